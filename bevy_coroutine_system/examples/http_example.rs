@@ -3,7 +3,7 @@
 //! This example shows how to make async HTTP requests within the coroutine system.
 //! Press SPACE to trigger an HTTP request that fetches data from a test API.
 
-#![feature(coroutines, coroutine_trait)]
+#![cfg_attr(not(feature = "async-backend"), feature(coroutines))]
 
 use bevy::prelude::*;
 use bevy_coroutine_system::prelude::*;
@@ -11,12 +11,12 @@ use bevy_coroutine_system::prelude::*;
 fn main() {
     let mut app = App::new();
     
-    app.add_plugins((DefaultPlugins, CoroutinePlugin))
+    app.add_plugins((DefaultPlugins, CoroutinePlugin::default()))
         .add_systems(Startup, setup)
         .add_systems(Update, trigger_request);
     
     // Register the coroutine system
-    app.register_coroutine(http_request_coroutine, http_request_coroutine::id());
+    app.add_coroutine(http_request_coroutine);
     
     app.run();
 }
@@ -68,64 +68,66 @@ fn trigger_request(
 }
 
 
-/// Coroutine that performs an async HTTP request
-#[coroutine_system]
-fn http_request_coroutine(
-    mut status_query: Query<&mut Text2d, (With<StatusText>, Without<ResponseText>)>,
-    mut response_query: Query<&mut Text2d, (With<ResponseText>, Without<StatusText>)>,
-) {
-    // Update status
-    for mut text in status_query.iter_mut() {
-        **text = "Sending HTTP request...".to_string();
-    }
+coroutine_systems! {
+    /// Coroutine that performs an async HTTP request
+    fn http_request_coroutine(
+        mut status_query: Query<&mut Text2d, (With<StatusText>, Without<ResponseText>)>,
+        mut response_query: Query<&mut Text2d, (With<ResponseText>, Without<StatusText>)>,
+    ) {
+        // Update status
+        for mut text in status_query.iter_mut() {
+            **text = "Sending HTTP request...".to_string();
+        }
     
-    // Clear previous response
-    for mut text in response_query.iter_mut() {
-        **text = "".to_string();
-    }
+        // Clear previous response
+        for mut text in response_query.iter_mut() {
+            **text = "".to_string();
+        }
     
-    // Make the async HTTP request
-    info!("Starting HTTP request...");
+        // Make the async HTTP request
+        info!("Starting HTTP request...");
     
-    // Use spawn_blocking_task to perform HTTP request in background thread
-    let response_result: Option<String> = yield spawn_blocking_task(move || {
-        let mut response = ureq::get("https://httpbin.org/json").call().unwrap();
-        response.body_mut().read_to_string().ok()
-    });
+        // Use spawn_blocking_task to perform HTTP request in background thread.
+        // If the task panics (e.g. the request fails), the result is `Err(TaskPanic)`
+        let response_result: Result<Option<String>, TaskPanic> = yield spawn_blocking_task(move || {
+            let mut response = ureq::get("https://httpbin.org/json").call().unwrap();
+            response.body_mut().read_to_string().ok()
+        });
     
-    // Process the response
-    match response_result {
-        Some(body) => {
-            info!("HTTP request successful!");
+        // Process the response
+        match response_result {
+            Ok(Some(body)) => {
+                info!("HTTP request successful!");
             
-            // Update status
-            for mut text in status_query.iter_mut() {
-                **text = "Request successful! Press SPACE to try again".to_string();
-            }
+                // Update status
+                for mut text in status_query.iter_mut() {
+                    **text = "Request successful! Press SPACE to try again".to_string();
+                }
             
-            // Show response (truncate if too long)
-            for mut text in response_query.iter_mut() {
-                let display_text = if body.len() > 500 {
-                    format!("{}...", &body[..500])
-                } else {
-                    body.clone()
-                };
-                **text = format!("Response:\n{}", display_text);
+                // Show response (truncate if too long)
+                for mut text in response_query.iter_mut() {
+                    let display_text = if body.len() > 500 {
+                        format!("{}...", &body[..500])
+                    } else {
+                        body.clone()
+                    };
+                    **text = format!("Response:\n{}", display_text);
+                }
             }
-        }
-        None => {
-            error!("HTTP request failed!");
+            Ok(None) | Err(_) => {
+                error!("HTTP request failed!");
             
-            // Update status
-            for mut text in status_query.iter_mut() {
-                **text = "Request failed! Press SPACE to try again".to_string();
-            }
+                // Update status
+                for mut text in status_query.iter_mut() {
+                    **text = "Request failed! Press SPACE to try again".to_string();
+                }
             
-            for mut text in response_query.iter_mut() {
-                **text = "Error: Failed to fetch data".to_string();
+                for mut text in response_query.iter_mut() {
+                    **text = "Error: Failed to fetch data".to_string();
+                }
             }
         }
-    }
     
-    info!("Coroutine completed!");
+        info!("Coroutine completed!");
+    }
 }
@@ -1,6 +1,6 @@
 //! 最小化的协程系统示例
 
-#![feature(coroutines, coroutine_trait)]
+#![cfg_attr(not(feature = "async-backend"), feature(coroutines))]
 
 use bevy::prelude::*;
 use bevy_coroutine_system::prelude::*;
@@ -9,9 +9,9 @@ use std::time::Duration;
 fn main() {
     let mut app = App::new();
     
-    app.add_plugins((MinimalPlugins, CoroutinePlugin));
+    app.add_plugins((MinimalPlugins, CoroutinePlugin::default()));
 
-    let id = app.register_coroutine(minimal_system, minimal_system::id());
+    let id = app.add_coroutine(minimal_system);
 
     println!("entities: {}", app.world().entities().len());
     
@@ -29,14 +29,15 @@ fn main() {
 }
 
 
-#[coroutine_system]
-fn minimal_system(mut commands: Commands) {
-    println!("System running - phase 1");
-    commands.spawn_empty();
+coroutine_systems! {
+    fn minimal_system(mut commands: Commands) {
+        println!("System running - phase 1");
+        commands.spawn_empty();
     
-    // 直接使用 yield 语句
-    yield sleep(Duration::from_secs(1));
+        // 直接使用 yield 语句
+        yield sleep(Duration::from_secs(1));
     
-    println!("System running - phase 2");
-    commands.spawn_empty();
+        println!("System running - phase 2");
+        commands.spawn_empty();
+    }
 }
@@ -3,7 +3,7 @@
 //! This example demonstrates how to use the coroutine system to create a continuous animation sequence.
 //! Press the spacebar to trigger the animation, and the box will perform a series of actions.
 
-#![feature(coroutines, coroutine_trait)]
+#![cfg_attr(not(feature = "async-backend"), feature(coroutines))]
 
 use bevy::prelude::*;
 use bevy_coroutine_system::prelude::*;
@@ -12,12 +12,12 @@ use std::time::Duration;
 fn main() {
     let mut app = App::new();
     
-    app.add_plugins((DefaultPlugins, CoroutinePlugin))
+    app.add_plugins((DefaultPlugins, CoroutinePlugin::default()))
         .add_systems(Startup, setup)
         .add_systems(Update, trigger_animation);
     
     // Register the coroutine system
-    app.register_coroutine(box_animation, box_animation::id());
+    app.add_coroutine(box_animation);
     
     app.run();
 }
@@ -68,75 +68,76 @@ fn trigger_animation(
     }
 }
 
-/// Coroutine animation sequence
-#[coroutine_system]
-fn box_animation(
-    mut box_query: Query<&mut Transform, With<AnimatedBox>>,
-    mut text_query: Query<&mut Text2d, With<StatusText>>,
-) {
-    // Start animation
-    info!("Animation started!");
-    
-    // Update text prompt
-    for mut text in text_query.iter_mut() {
-        **text = "Scaling up...".to_string();
-    }
+coroutine_systems! {
+    /// Coroutine animation sequence
+    fn box_animation(
+        mut box_query: Query<&mut Transform, With<AnimatedBox>>,
+        mut text_query: Query<&mut Text2d, With<StatusText>>,
+    ) {
+        // Start animation
+        info!("Animation started!");
+    
+        // Update text prompt
+        for mut text in text_query.iter_mut() {
+            **text = "Scaling up...".to_string();
+        }
     
-    // Phase 1: Scale up
-    for _ in 0..30 {
-        yield next_frame();
-        for mut transform in box_query.iter_mut() {
-            transform.scale *= 1.02;
+        // Phase 1: Scale up
+        for _ in 0..30 {
+            yield next_frame();
+            for mut transform in box_query.iter_mut() {
+                transform.scale *= 1.02;
+            }
         }
-    }
     
-    // Wait a moment
-    yield sleep(Duration::from_millis(300));
+        // Wait a moment
+        yield sleep(Duration::from_millis(300));
     
-    // Update text
-    for mut text in text_query.iter_mut() {
-        **text = "Moving and rotating...".to_string();
-    }
+        // Update text
+        for mut text in text_query.iter_mut() {
+            **text = "Moving and rotating...".to_string();
+        }
     
-    // Phase 2: Move and rotate
-    for _ in 0..60 {
-        yield next_frame();
-        for mut transform in box_query.iter_mut() {
-            transform.translation.x += 2.0;
-            transform.rotate_z(0.02);
+        // Phase 2: Move and rotate
+        for _ in 0..60 {
+            yield next_frame();
+            for mut transform in box_query.iter_mut() {
+                transform.translation.x += 2.0;
+                transform.rotate_z(0.02);
+            }
         }
-    }
     
-    // Wait
-    yield sleep(Duration::from_millis(500));
+        // Wait
+        yield sleep(Duration::from_millis(500));
     
-    // Update text
-    for mut text in text_query.iter_mut() {
-        **text = "Returning...".to_string();
-    }
+        // Update text
+        for mut text in text_query.iter_mut() {
+            **text = "Returning...".to_string();
+        }
     
-    // Phase 3: Return and scale down
-    for _ in 0..60 {
-        yield next_frame();
-        for mut transform in box_query.iter_mut() {
-            transform.translation.x -= 2.0;
-            transform.rotate_z(-0.02);
+        // Phase 3: Return and scale down
+        for _ in 0..60 {
+            yield next_frame();
+            for mut transform in box_query.iter_mut() {
+                transform.translation.x -= 2.0;
+                transform.rotate_z(-0.02);
+            }
         }
-    }
     
-    // Finally restore size
-    for _ in 0..30 {
-        yield next_frame();
-        for mut transform in box_query.iter_mut() {
-            transform.scale /= 1.02;
+        // Finally restore size
+        for _ in 0..30 {
+            yield next_frame();
+            for mut transform in box_query.iter_mut() {
+                transform.scale /= 1.02;
+            }
         }
-    }
-    yield noop();
+        yield noop();
     
-    // Complete
-    for mut text in text_query.iter_mut() {
-        **text = "Animation complete! Press SPACE to restart".to_string();
-    }
+        // Complete
+        for mut text in text_query.iter_mut() {
+            **text = "Animation complete! Press SPACE to restart".to_string();
+        }
     
-    info!("Animation completed!");
+        info!("Animation completed!");
+    }
 }
\ No newline at end of file
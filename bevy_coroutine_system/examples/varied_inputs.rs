@@ -1,11 +1,19 @@
 //! An example to showcase that the macro can handle varied inputs.
 
-#![feature(coroutines, coroutine_trait)]
+#![cfg_attr(not(feature = "async-backend"), feature(coroutines))]
 
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy_coroutine_system::prelude::*;
 
 fn main() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, CoroutinePlugin::default()));
+
+    app.add_coroutine(simple_coroutine);
+    app.add_coroutine(any_system_param);
+    app.add_coroutine(destructured_params);
+
     println!("OK PASS!");
 }
 
@@ -18,6 +26,19 @@ struct Player;
 #[derive(Event, Clone)]
 struct AnEvent;
 
+#[derive(Resource, Default)]
+struct Score(u32);
+
+/// A custom system param, its lifetimes are not visible to the macro
+#[derive(SystemParam)]
+struct PlayerInfo<'w, 's> {
+    players: Query<'w, 's, Entity, With<Player>>,
+    score: Res<'w, Score>,
+}
+
+/// A type alias hiding the lifetimes of `Query`
+type PlayerTransforms<'w, 's> = Query<'w, 's, &'static Transform, With<Player>>;
+
 /// 测试不同类型的输入
 /// 都可以被宏正确解析
 /// 保证修改过程中，功能没有被破坏
@@ -31,3 +52,35 @@ fn simple_coroutine(
     mut commands: Commands,
     a: Local<i32>,
 ) {}
+
+/// Any `SystemParam` works, independent of how its type is spelled
+#[coroutine_system]
+fn any_system_param(
+    player: Single<&Transform, With<Player>>,
+    score: Option<Res<Score>>,
+    mut set: ParamSet<(Query<&mut Transform>, Query<&H>)>,
+    populated: Populated<&H>,
+    assets: Res<AssetServer>,
+    info: PlayerInfo,
+    transforms: PlayerTransforms,
+    non_send: NonSend<()>,
+    gizmos: Gizmos,
+) {
+    let _players = info.players.iter().count() as u32 + info.score.0;
+}
+
+coroutine_systems! {
+    /// Parameters can be destructured like in regular systems
+    fn destructured_params(
+        (time, mut score): (Res<Time>, ResMut<Score>),
+        PlayerInfo { players, .. }: PlayerInfo,
+        _: Query<&H>,
+    ) {
+        score.0 += players.iter().count() as u32;
+
+        yield next_frame();
+
+        // The bindings point to the params of the current run after each yield
+        score.0 += time.delta().as_millis() as u32;
+    }
+}
@@ -0,0 +1,165 @@
+//! 协程的两种实现
+//!
+//! 默认使用 nightly 的协程，启用 `async-backend` feature 后协程体被转换为 async 状态机，
+//! 可以在 stable Rust 上使用。两者都通过 [`ResumeCoroutine`] 被 [`CoroutineTask`](crate::CoroutineTask) 推进
+
+use crate::{CoroutineFuture, CoroutineResult, IntoCoroutineResult};
+use std::pin::Pin;
+
+/// 协程恢复一次的结果
+pub enum CoroutineStep {
+    /// 协程 yield 出一个 Future，等它完成后再恢复
+    Yielded(CoroutineFuture),
+    /// 协程已结束
+    Complete(CoroutineResult),
+}
+
+/// 可以恢复的协程，`R` 是每次恢复时传入的输入
+pub trait ResumeCoroutine<R> {
+    /// 以 `arg` 为输入恢复协程，直到它 yield 或结束
+    fn resume(self: Pin<&mut Self>, arg: R) -> CoroutineStep;
+}
+
+/// 协程实例的类型
+pub type BoxedCoroutine<R> = Pin<Box<dyn ResumeCoroutine<R> + Send>>;
+
+#[cfg(not(feature = "async-backend"))]
+pub(crate) use nightly::CoroutineBody;
+
+#[cfg(feature = "async-backend")]
+pub use async_await::{CoroutineYielder, YieldFuture};
+#[cfg(feature = "async-backend")]
+pub(crate) use async_await::AsyncCoroutine;
+
+#[cfg(not(feature = "async-backend"))]
+mod nightly {
+    use super::*;
+    use std::ops::{Coroutine, CoroutineState};
+
+    /// 将协程函数体的返回值统一转换为 [`CoroutineResult`]
+    pub(crate) struct CoroutineBody<C>(pub C);
+
+    impl<R, C> ResumeCoroutine<R> for CoroutineBody<C>
+    where
+        C: Coroutine<R, Yield = CoroutineFuture>,
+        C::Return: IntoCoroutineResult,
+    {
+        fn resume(self: Pin<&mut Self>, arg: R) -> CoroutineStep {
+            // SAFETY: 内部协程是结构化固定的，不会被移动
+            let coroutine = unsafe { self.map_unchecked_mut(|body| &mut body.0) };
+            match coroutine.resume(arg) {
+                CoroutineState::Yielded(fut) => CoroutineStep::Yielded(fut),
+                CoroutineState::Complete(result) => CoroutineStep::Complete(result.into_coroutine_result()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async-backend")]
+mod async_await {
+    use super::*;
+    use std::future::Future;
+    use std::sync::{Arc, Mutex, MutexGuard};
+    use std::task::{Context, Poll, Waker};
+
+    /// 协程体与推进它的任务之间交换的数据
+    struct YieldSlot<R> {
+        /// 这次恢复传入的输入
+        input: Option<R>,
+        /// 协程体 yield 出的 Future
+        yielded: Option<CoroutineFuture>,
+    }
+
+    fn lock<R>(slot: &Mutex<YieldSlot<R>>) -> MutexGuard<'_, YieldSlot<R>> {
+        slot.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 在 async 协程体中 yield 的句柄
+    ///
+    /// 宏生成的协程体用它取得每次恢复的输入，`yield fut` 被转换为 `yielder.yield_future(fut).await`
+    pub struct CoroutineYielder<R> {
+        slot: Arc<Mutex<YieldSlot<R>>>,
+    }
+
+    impl<R> CoroutineYielder<R> {
+        /// 取出这次恢复传入的输入
+        ///
+        /// # Panics
+        /// 同一次恢复中已经取出过输入时 panic
+        pub fn input(&self) -> R {
+            lock(&self.slot)
+                .input
+                .take()
+                .expect("coroutine input taken twice in one resume")
+        }
+
+        /// yield 一个 Future，协程下一次恢复时返回新的输入
+        pub fn yield_future(&self, fut: CoroutineFuture) -> YieldFuture<R> {
+            YieldFuture {
+                slot: self.slot.clone(),
+                fut: Some(fut),
+            }
+        }
+    }
+
+    /// [`CoroutineYielder::yield_future`] 返回的 Future
+    ///
+    /// 第一次轮询时交出 yield 的 Future 并挂起协程体，下一次恢复时完成并返回新的输入
+    pub struct YieldFuture<R> {
+        slot: Arc<Mutex<YieldSlot<R>>>,
+        fut: Option<CoroutineFuture>,
+    }
+
+    impl<R> Future for YieldFuture<R> {
+        type Output = R;
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<R> {
+            let this = &mut *self;
+            let mut slot = lock(&this.slot);
+            if let Some(fut) = this.fut.take() {
+                slot.yielded = Some(fut);
+                return Poll::Pending;
+            }
+            Poll::Ready(slot.input.take().expect("coroutine resumed without input"))
+        }
+    }
+
+    /// 由 async 协程体实现的协程
+    pub(crate) struct AsyncCoroutine<R, F> {
+        slot: Arc<Mutex<YieldSlot<R>>>,
+        body: Pin<Box<F>>,
+    }
+
+    impl<R, F: Future> AsyncCoroutine<R, F> {
+        /// 用 `body` 创建协程，`body` 接收协程体中使用的 [`CoroutineYielder`]
+        pub(crate) fn new(body: impl FnOnce(CoroutineYielder<R>) -> F) -> Self {
+            let slot = Arc::new(Mutex::new(YieldSlot {
+                input: None,
+                yielded: None,
+            }));
+            let body = Box::pin(body(CoroutineYielder { slot: slot.clone() }));
+            Self { slot, body }
+        }
+    }
+
+    impl<R, F> ResumeCoroutine<R> for AsyncCoroutine<R, F>
+    where
+        F: Future,
+        F::Output: IntoCoroutineResult,
+    {
+        fn resume(mut self: Pin<&mut Self>, arg: R) -> CoroutineStep {
+            lock(&self.slot).input = Some(arg);
+            // 协程体只会在 yield 处挂起，yield 的 Future 由任务用协程的唤醒器轮询
+            let mut cx = Context::from_waker(Waker::noop());
+            match self.body.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => CoroutineStep::Complete(result.into_coroutine_result()),
+                Poll::Pending => CoroutineStep::Yielded(
+                    lock(&self.slot)
+                        .yielded
+                        .take()
+                        .expect("async coroutine bodies can only be suspended by yield"),
+                ),
+            }
+        }
+    }
+}
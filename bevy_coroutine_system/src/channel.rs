@@ -0,0 +1,265 @@
+//! 协程与系统之间的通道
+//!
+//! [`coroutine_channel`] 创建的通道没有容量上限，发送不会阻塞，可以在任意系统、线程或协程中进行；
+//! 接收端的 [`recv`](CoroutineReceiver::recv) 可以在协程中 yield，有数据时唤醒等待的协程，
+//! 在普通系统中则使用 [`try_recv`](CoroutineReceiver::try_recv)。
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 通道两端共享的状态
+struct Shared<T> {
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    /// 等待数据的协程
+    waiters: Vec<Waker>,
+    senders: usize,
+    receivers: usize,
+}
+
+impl<T> Shared<T> {
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 唤醒所有等待数据的协程，它们会重新检查通道
+fn wake_all(waiters: Vec<Waker>) {
+    waiters.into_iter().for_each(Waker::wake);
+}
+
+/// 创建一个在协程与系统之间传递 `T` 的通道
+///
+/// 两端都可以克隆：多个发送端的数据进入同一个队列，多个接收端各自取走其中的一部分。
+///
+/// # Example
+/// ```rust,ignore
+/// #[derive(Resource)]
+/// struct Jobs(CoroutineSender<Job>, CoroutineReceiver<Job>);
+///
+/// fn setup(mut commands: Commands) {
+///     let (sender, receiver) = coroutine_channel();
+///     commands.insert_resource(Jobs(sender, receiver));
+/// }
+///
+/// #[coroutine_system]
+/// fn worker(jobs: Res<Jobs>) {
+///     let receiver = jobs.1.clone();
+///     // 所有发送端都被丢弃后得到 None
+///     while let Some(job) = yield receiver.recv() {
+///         job.run();
+///     }
+/// }
+/// ```
+pub fn coroutine_channel<T>() -> (CoroutineSender<T>, CoroutineReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            waiters: Vec::new(),
+            senders: 1,
+            receivers: 1,
+        }),
+    });
+    (
+        CoroutineSender { shared: shared.clone() },
+        CoroutineReceiver { shared },
+    )
+}
+
+/// 通道的发送端，由 [`coroutine_channel`] 创建
+pub struct CoroutineSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> CoroutineSender<T> {
+    /// 发送数据并唤醒等待的协程
+    ///
+    /// 所有接收端都已被丢弃时返回 `Err`，其中包含没有发送出去的数据
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let waiters = {
+            let mut state = self.shared.state();
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+            state.queue.push_back(value);
+            std::mem::take(&mut state.waiters)
+        };
+        wake_all(waiters);
+        Ok(())
+    }
+
+    /// 所有接收端是否都已被丢弃
+    pub fn is_closed(&self) -> bool {
+        self.shared.state().receivers == 0
+    }
+}
+
+impl<T> Clone for CoroutineSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for CoroutineSender<T> {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.shared.state();
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            std::mem::take(&mut state.waiters)
+        };
+        // 最后一个发送端被丢弃，等待的协程会收到 None
+        wake_all(waiters);
+    }
+}
+
+impl<T> fmt::Debug for CoroutineSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoroutineSender").finish_non_exhaustive()
+    }
+}
+
+/// 通道的接收端，由 [`coroutine_channel`] 创建
+pub struct CoroutineReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> CoroutineReceiver<T> {
+    /// 等待下一个数据，可以在协程中 yield
+    ///
+    /// 输出 `Some(value)`；队列为空且所有发送端都已被丢弃时输出 `None`
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let job: Option<Job> = yield receiver.recv();
+    /// ```
+    pub fn recv(&self) -> Recv<T> {
+        Recv {
+            receiver: self.clone(),
+        }
+    }
+
+    /// 不等待地取出下一个数据，适合在普通系统中使用
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state();
+        match state.queue.pop_front() {
+            Some(value) => Ok(value),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// 队列中的数据数量
+    pub fn len(&self) -> usize {
+        self.shared.state().queue.len()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for CoroutineReceiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.state().receivers += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for CoroutineReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state().receivers -= 1;
+    }
+}
+
+impl<T> fmt::Debug for CoroutineReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoroutineReceiver")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// [`CoroutineReceiver::recv`] 返回的 Future
+///
+/// 持有接收端的一个克隆，不借用创建它的接收端，因此可以跨越 yield
+pub struct Recv<T> {
+    receiver: CoroutineReceiver<T>,
+}
+
+impl<T> Future for Recv<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.receiver.shared.state();
+        if let Some(value) = state.queue.pop_front() {
+            return Poll::Ready(Some(value));
+        }
+        if state.senders == 0 {
+            return Poll::Ready(None);
+        }
+        if !state.waiters.iter().any(|waiter| waiter.will_wake(cx.waker())) {
+            state.waiters.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> fmt::Debug for Recv<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recv").finish_non_exhaustive()
+    }
+}
+
+/// 所有接收端都已被丢弃时 [`CoroutineSender::send`] 返回的错误，包含没有发送出去的数据
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a closed coroutine channel")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// [`CoroutineReceiver::try_recv`] 返回的错误
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// 队列为空
+    Empty,
+    /// 队列为空且所有发送端都已被丢弃
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("receiving on an empty coroutine channel"),
+            Self::Disconnected => f.write_str("receiving on an empty and disconnected coroutine channel"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
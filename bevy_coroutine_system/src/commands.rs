@@ -0,0 +1,170 @@
+//! 在任意系统、观察者或独占上下文中启动和取消协程
+//!
+//! 启动协程就是运行一次协程系统，协程已经在运行时按它的 [`CoroutineRetrigger`](crate::CoroutineRetrigger) 处理
+
+use crate::{CoroutineId, RunningCoroutines};
+use bevy::ecs::component::HookContext;
+use bevy::ecs::system::{EntityCommands, IsExclusiveFunctionSystem, IsFunctionSystem, RegisteredSystemError};
+use bevy::ecs::world::DeferredWorld;
+use bevy::prelude::*;
+use std::any::type_name;
+
+/// [`Commands`] 的协程扩展方法
+///
+/// # Example
+/// ```rust,ignore
+/// fn on_click(trigger: Trigger<Pointer<Click>>, mut commands: Commands) {
+///     commands.start_coroutine(show_dialog);
+///     commands.cancel_coroutine(idle_animation::id());
+/// }
+/// ```
+pub trait CoroutineCommandsExt {
+    /// 在命令应用时启动协程系统 `system`
+    fn start_coroutine<M: 'static>(&mut self, system: impl IntoSystem<(), (), M> + Send + 'static);
+
+    /// 在命令应用时取消协程，协程未在运行时什么也不做
+    fn cancel_coroutine(&mut self, id: CoroutineId);
+}
+
+impl CoroutineCommandsExt for Commands<'_, '_> {
+    fn start_coroutine<M: 'static>(&mut self, system: impl IntoSystem<(), (), M> + Send + 'static) {
+        self.run_system_cached(system);
+    }
+
+    fn cancel_coroutine(&mut self, id: CoroutineId) {
+        self.queue(move |world: &mut World| world.cancel_coroutine(id));
+    }
+}
+
+/// [`World`] 的协程扩展方法
+pub trait CoroutineWorldExt {
+    /// 立即启动协程系统 `system`，运行到第一个 yield 或结束
+    fn start_coroutine<M: 'static>(
+        &mut self,
+        system: impl IntoSystem<(), (), M> + 'static,
+    ) -> Result<(), RegisteredSystemError<()>>;
+
+    /// 取消协程，协程未在运行时什么也不做
+    fn cancel_coroutine(&mut self, id: CoroutineId);
+}
+
+impl CoroutineWorldExt for World {
+    fn start_coroutine<M: 'static>(
+        &mut self,
+        system: impl IntoSystem<(), (), M> + 'static,
+    ) -> Result<(), RegisteredSystemError<()>> {
+        self.run_system_cached(system)
+    }
+
+    fn cancel_coroutine(&mut self, id: CoroutineId) {
+        if let Some(mut running) = self.get_resource_mut::<RunningCoroutines>() {
+            running.cancel(id);
+        }
+    }
+}
+
+/// [`EntityCommands`] 的协程扩展方法
+pub trait CoroutineEntityCommandsExt {
+    /// 启动协程系统 `system`，并让这个实体成为它的拥有者之一
+    ///
+    /// 协程在同一时间只有一个实例，多个实体启动同一个协程时它们共同拥有这个实例，
+    /// 所有拥有者都被销毁或移除了 [`OwnedCoroutines`] 后协程才被取消。
+    /// 协程可以通过 `Query<(Entity, &OwnedCoroutines)>` 和 [`OwnedCoroutines::contains`] 找到它的拥有者
+    ///
+    /// `system` 必须是协程系统函数本身，经过 `pipe`、`map` 等组合的系统无法得到协程的标识符
+    fn start_coroutine<M: 'static, S>(&mut self, system: S) -> &mut Self
+    where
+        S: CoroutineSystemFn<M> + Send + 'static;
+}
+
+/// 可以由类型得到协程标识符的系统函数
+///
+/// 协程系统函数的标识符由函数的类型得到，与宏生成的 `id()` 相同。
+/// 只为普通的系统函数和独占系统函数实现，组合出的系统不会运行在函数自己的标识符下
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a coroutine system function",
+    note = "an entity can only own a `#[coroutine_system]` function passed directly, not a piped or mapped system"
+)]
+pub trait CoroutineSystemFn<M>: IntoSystem<(), (), M> {}
+
+impl<Marker, F> CoroutineSystemFn<(IsFunctionSystem, Marker)> for F where
+    F: IntoSystem<(), (), (IsFunctionSystem, Marker)>
+{
+}
+
+impl<Marker, F> CoroutineSystemFn<(IsExclusiveFunctionSystem, Marker)> for F where
+    F: IntoSystem<(), (), (IsExclusiveFunctionSystem, Marker)>
+{
+}
+
+impl CoroutineEntityCommandsExt for EntityCommands<'_> {
+    fn start_coroutine<M: 'static, S>(&mut self, system: S) -> &mut Self
+    where
+        S: CoroutineSystemFn<M> + Send + 'static,
+    {
+        // 协程的标识符只比较类型，直接由系统函数得到
+        let id = CoroutineId::of(&system, type_name::<S>());
+        self.queue(move |mut entity: EntityWorldMut| {
+            match entity.get_mut::<OwnedCoroutines>() {
+                Some(mut owned) => {
+                    if !owned.contains(id) {
+                        owned.0.push(id);
+                    }
+                }
+                None => {
+                    entity.insert(OwnedCoroutines(vec![id]));
+                }
+            }
+            let owner = entity.id();
+            entity.world_scope(|world| {
+                if let Some(mut running) = world.get_resource_mut::<RunningCoroutines>() {
+                    running.add_owner(id, owner);
+                }
+                if let Err(e) = world.run_system_cached(system) {
+                    warn!("failed to start coroutine {id}: {e}");
+                }
+            });
+        });
+        self
+    }
+}
+
+/// 实体拥有的协程
+///
+/// 由 [`CoroutineEntityCommandsExt::start_coroutine`] 添加。组件被移除或实体被销毁时，
+/// 实体不再拥有这些协程，没有其他拥有者的协程会被取消
+///
+/// # Example
+/// ```rust,ignore
+/// #[coroutine_system]
+/// fn blink(mut owners: Query<(&OwnedCoroutines, &mut Visibility)>) {
+///     for (owned, mut visibility) in owners.iter_mut() {
+///         if owned.contains(blink::id()) {
+///             visibility.toggle_visible_hidden();
+///         }
+///     }
+///     yield sleep(Duration::from_millis(500));
+/// }
+/// ```
+#[derive(Component, Debug, Default)]
+#[component(on_remove = release_owned_coroutines)]
+pub struct OwnedCoroutines(pub Vec<CoroutineId>);
+
+impl OwnedCoroutines {
+    /// 实体是否拥有协程 `id`
+    pub fn contains(&self, id: CoroutineId) -> bool {
+        self.0.contains(&id)
+    }
+}
+
+fn release_owned_coroutines(mut world: DeferredWorld, context: HookContext) {
+    let Some(owned) = world.get::<OwnedCoroutines>(context.entity) else {
+        return;
+    };
+    let ids = owned.0.clone();
+    if let Some(mut running) = world.get_resource_mut::<RunningCoroutines>() {
+        for id in ids {
+            running.release_owner(id, context.entity);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! 协程系统的配置
+//!
+//! 通常由 `#[coroutine_system(...)]` 的参数生成，协程启动时随协程一起登记到 [`RunningCoroutines`](crate::RunningCoroutines)。
+
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
+use bevy::prelude::*;
+use std::sync::Arc;
+
+/// 协程运行中再次被触发时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CoroutineRetrigger {
+    /// 立即恢复正在运行的协程
+    #[default]
+    Resume,
+    /// 忽略这次触发，协程继续运行
+    Ignore,
+    /// 取消正在运行的协程并重新开始
+    Restart,
+}
+
+/// 判断协程是否处于所属状态中的函数
+pub type StateScope = Arc<dyn Fn(&World) -> bool + Send + Sync>;
+
+/// 协程系统的配置
+///
+/// # Example
+/// ```rust,ignore
+/// #[coroutine_system(schedule = FixedUpdate, retrigger = Restart, repeat, state = GameState::Playing, tags = ["ai"], priority = 10)]
+/// fn patrol(mut query: Query<&mut Transform, With<Guard>>) {
+///     // ...
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CoroutineConfig {
+    /// 驱动协程的调度，协程被唤醒后在这个调度中恢复，默认为 `Update`
+    pub schedule: InternedScheduleLabel,
+    /// 协程运行中再次被触发时的处理方式
+    pub retrigger: CoroutineRetrigger,
+    /// 协程成功结束后是否在下一帧自动重新开始
+    pub repeat: bool,
+    /// 协程所属的状态，离开该状态时协程会被取消
+    pub state_scope: Option<StateScope>,
+    /// 协程的标签，可以用来查找一组协程
+    pub tags: &'static [&'static str],
+    /// 协程的优先级，同一调度中被唤醒的协程按优先级从高到低恢复，优先级相同时按登记顺序，默认为 0
+    pub priority: i32,
+}
+
+impl Default for CoroutineConfig {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+            retrigger: CoroutineRetrigger::default(),
+            repeat: false,
+            state_scope: None,
+            tags: &[],
+            priority: 0,
+        }
+    }
+}
+
+impl CoroutineConfig {
+    /// 设置驱动协程的调度
+    pub fn schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+
+    /// 设置协程运行中再次被触发时的处理方式
+    pub fn retrigger(mut self, retrigger: CoroutineRetrigger) -> Self {
+        self.retrigger = retrigger;
+        self
+    }
+
+    /// 设置协程成功结束后是否自动重新开始
+    pub fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// 设置协程所属的状态，离开该状态时协程会被取消
+    pub fn state_scope<S: States>(mut self, state: S) -> Self {
+        self.state_scope = Some(Arc::new(move |world: &World| {
+            world
+                .get_resource::<State<S>>()
+                .is_some_and(|current| *current.get() == state)
+        }));
+        self
+    }
+
+    /// 设置协程的标签
+    pub fn tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// 设置协程的优先级
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 协程是否处于它所属的状态中，没有设置状态时总是返回 `true`
+    pub fn in_scope(&self, world: &World) -> bool {
+        self.state_scope.as_ref().is_none_or(|in_state| in_state(world))
+    }
+}
+
+impl std::fmt::Debug for CoroutineConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoroutineConfig")
+            .field("schedule", &self.schedule)
+            .field("retrigger", &self.retrigger)
+            .field("repeat", &self.repeat)
+            .field("state_scope", &self.state_scope.is_some())
+            .field("tags", &self.tags)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
@@ -0,0 +1,84 @@
+//! 协程的诊断信息
+//!
+//! [`CoroutineDiagnosticsPlugin`] 把协程的运行情况登记为 Bevy 的 [`Diagnostic`]，
+//! 可以配合 `LogDiagnosticsPlugin` 输出，或在调试界面中查看。
+
+use crate::{background_tasks_in_flight, RunningCoroutines};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// 一帧中推进协程的统计
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ResumeStats {
+    /// 推进的次数
+    pub resumes: u32,
+    /// 推进花费的总时间
+    pub total: Duration,
+    /// 单次推进花费的最长时间
+    pub max: Duration,
+}
+
+impl ResumeStats {
+    pub(crate) fn record(&mut self, elapsed: Duration) {
+        self.resumes += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+}
+
+/// 登记协程诊断信息的插件
+///
+/// 需要同时添加 `CoroutinePlugin`
+///
+/// # Example
+/// ```rust,ignore
+/// app.add_plugins((
+///     CoroutinePlugin::default(),
+///     CoroutineDiagnosticsPlugin,
+///     LogDiagnosticsPlugin::default(),
+/// ));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CoroutineDiagnosticsPlugin;
+
+impl Plugin for CoroutineDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(Self::ACTIVE_COROUTINES))
+            .register_diagnostic(Diagnostic::new(Self::RESUMES))
+            .register_diagnostic(Diagnostic::new(Self::RESUME_TIME_AVG).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::RESUME_TIME_MAX).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::BACKGROUND_TASKS))
+            .add_systems(Last, Self::diagnostic_system);
+    }
+}
+
+impl CoroutineDiagnosticsPlugin {
+    /// 运行中的协程数量
+    pub const ACTIVE_COROUTINES: DiagnosticPath = DiagnosticPath::const_new("coroutine/active");
+    /// 这一帧推进协程的次数
+    pub const RESUMES: DiagnosticPath = DiagnosticPath::const_new("coroutine/resumes");
+    /// 这一帧单次推进协程的平均时间，单位为毫秒
+    pub const RESUME_TIME_AVG: DiagnosticPath = DiagnosticPath::const_new("coroutine/resume_time_avg");
+    /// 这一帧单次推进协程的最长时间，单位为毫秒
+    pub const RESUME_TIME_MAX: DiagnosticPath = DiagnosticPath::const_new("coroutine/resume_time_max");
+    /// 正在执行或排队等待的后台任务数量
+    pub const BACKGROUND_TASKS: DiagnosticPath = DiagnosticPath::const_new("coroutine/background_tasks");
+
+    /// 在每帧结束时记录这一帧的诊断信息
+    pub fn diagnostic_system(mut diagnostics: Diagnostics, running_task: Res<RunningCoroutines>) {
+        let stats = running_task.resume_stats;
+        diagnostics.add_measurement(&Self::ACTIVE_COROUTINES, || running_task.systems.len() as f64);
+        diagnostics.add_measurement(&Self::RESUMES, || stats.resumes as f64);
+        diagnostics.add_measurement(&Self::BACKGROUND_TASKS, || background_tasks_in_flight() as f64);
+
+        // 没有推进协程的帧不计入推进时间
+        if stats.resumes == 0 {
+            return;
+        }
+        diagnostics.add_measurement(&Self::RESUME_TIME_AVG, || {
+            stats.total.as_secs_f64() * 1000.0 / stats.resumes as f64
+        });
+        diagnostics.add_measurement(&Self::RESUME_TIME_MAX, || stats.max.as_secs_f64() * 1000.0);
+    }
+}
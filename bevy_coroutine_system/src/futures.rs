@@ -0,0 +1,186 @@
+//! 协程中可以 yield 的内置 Future，以及将任意 Future 转换为协程 Future 的工具
+//!
+//! 任何 `Future + Send + 'static` 都可以直接在协程中 yield，
+//! 宏会通过 [`into_coroutine_future`] 在内部完成类型擦除。
+
+use crate::{CoroutineContext, CoroutineFuture, YieldSite};
+use std::any::Any;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::panic::Location;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// 将任意 Future 转换为协程可以挂起等待的 [`CoroutineFuture`]
+///
+/// Future 的输出会被装箱为 `Box<dyn Any + Send>`，通常不需要手动使用。
+pub fn into_coroutine_future<F>(future: F) -> CoroutineFuture
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Box::pin(async move { Box::new(future.await) as Box<dyn Any + Send> })
+}
+
+/// yield 表达式结果的类型标记
+///
+/// 由 [`typed_coroutine_future`] 与擦除类型后的 Future 一同返回，
+/// 恢复协程后通过 [`CoroutineTaskInput::take_result`](crate::CoroutineTaskInput::take_result)
+/// 取回 Future 的输出，从而在编译期把 `let` 绑定的类型与 Future 的 `Output` 关联起来。
+pub struct YieldOutput<T>(PhantomData<fn() -> T>);
+
+impl<T> std::fmt::Debug for YieldOutput<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("YieldOutput")
+            .field(&std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+/// 将任意 Future 转换为 [`CoroutineFuture`]，同时返回其输出类型的标记
+///
+/// `#[coroutine_system]` 会对每个 `yield` 的表达式自动调用它，
+/// 在协程中调用时会记录 yield 的位置和 Future 的类型，参见 [`CoroutineInfo`](crate::CoroutineInfo)
+#[track_caller]
+pub fn typed_coroutine_future<F>(future: F) -> (CoroutineFuture, YieldOutput<F::Output>)
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    CoroutineContext::record_yield(YieldSite {
+        location: Location::caller(),
+        future: std::any::type_name::<F>(),
+    });
+    (into_coroutine_future(future), YieldOutput(PhantomData))
+}
+
+/// [`sleep`] 返回的 Future，输出唤醒时的时间戳
+#[derive(Debug)]
+pub struct Sleep {
+    target_time: Instant,
+}
+
+impl Future for Sleep {
+    type Output = Instant;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let now = CoroutineContext::now();
+        if now >= self.target_time {
+            return Poll::Ready(now);
+        }
+        // 登记定时器，到期后由驱动系统唤醒；不在协程中时只能等待下一次轮询
+        CoroutineContext::with_current(|context| match context {
+            Some(context) => context.timers.register(self.target_time, cx.waker().clone()),
+            None => cx.waker().wake_by_ref(),
+        });
+        Poll::Pending
+    }
+}
+
+/// 创建一个睡眠Future
+///
+/// 在协程中使用协程的时钟计时，参见 [`CoroutineClock`](crate::CoroutineClock)
+///
+/// # Example
+/// ```rust,ignore
+/// yield sleep(Duration::from_secs(1));
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        target_time: CoroutineContext::now() + duration,
+    }
+}
+
+/// [`next_frame`] 返回的 Future
+#[derive(Debug)]
+pub struct NextFrame {
+    first_poll: bool,
+}
+
+impl Future for NextFrame {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.first_poll {
+            self.first_poll = false;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+/// 创建一个等待下一帧的Future
+///
+/// 第一次poll时唤醒自身并返回Pending，下一帧被驱动时返回Ready
+///
+/// # Example
+/// ```rust,ignore
+/// yield next_frame();
+/// ```
+pub fn next_frame() -> NextFrame {
+    NextFrame { first_poll: true }
+}
+
+/// [`noop`] 返回的 Future
+#[derive(Debug)]
+pub struct Noop;
+
+impl Future for Noop {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Poll::Ready(())
+    }
+}
+
+/// 创建一个空操作（no-op）的 Future
+///
+/// 这个函数立即返回，不执行任何操作。主要用于在协程中创建一个 yield 点，
+/// 帮助解决借用检查问题
+///
+/// # Example
+/// ```rust,ignore
+/// // 在两个可能有借用冲突的代码块之间使用
+/// yield noop();
+/// ```
+pub fn noop() -> Noop {
+    Noop
+}
+
+/// [`yield_if_over_budget`] 返回的 Future
+#[derive(Debug)]
+pub struct YieldIfOverBudget {
+    first_poll: bool,
+}
+
+impl Future for YieldIfOverBudget {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.first_poll && CoroutineContext::is_over_budget() {
+            self.first_poll = false;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(())
+    }
+}
+
+/// 这一帧的预算用完时等待下一帧，否则立即继续
+///
+/// 预算通过 `CoroutinePlugin` 的 `frame_budget` 设置，未设置时总是立即继续。
+/// 适合在耗时的循环中定期调用，把工作分散到多帧
+///
+/// # Example
+/// ```rust,ignore
+/// for chunk in chunks {
+///     generate(chunk);
+///     yield yield_if_over_budget();
+/// }
+/// ```
+pub fn yield_if_over_budget() -> YieldIfOverBudget {
+    YieldIfOverBudget { first_poll: true }
+}
@@ -0,0 +1,84 @@
+//! 协程系统的标识符
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+
+/// 协程系统的唯一标识符
+///
+/// 由协程系统函数的类型得到，不同的函数和同一泛型函数的不同实例互不相同，
+/// 比较和哈希只使用类型，附带的名称用于日志和错误信息
+///
+/// # Example
+/// ```rust,ignore
+/// let id: CoroutineId = my_coroutine_system::id();
+/// info!("{id}"); // "my_game::my_coroutine_system"
+/// ```
+#[derive(Clone, Copy)]
+pub struct CoroutineId {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl CoroutineId {
+    /// 获取协程系统函数 `system` 的标识符
+    pub fn of<F: 'static>(_system: &F, name: &'static str) -> Self {
+        Self {
+            type_id: TypeId::of::<F>(),
+            name,
+        }
+    }
+
+    /// 可读的名称，默认为 "module_path::function_name"，或宏参数中指定的 `name`
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl PartialEq for CoroutineId {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+    }
+}
+
+impl Eq for CoroutineId {}
+
+impl Hash for CoroutineId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_id.hash(state);
+    }
+}
+
+impl fmt::Debug for CoroutineId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CoroutineId").field(&self.name).finish()
+    }
+}
+
+impl fmt::Display for CoroutineId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// 泛型协程系统的名称
+///
+/// `#[coroutine_system]` 为泛型函数生成的 `id` 函数会调用它，`system` 是协程系统函数的泛型实例，
+/// 名称的格式为 `base<A, B>`。`args` 只在第一次获取该实例的名称时调用。
+pub fn generic_coroutine_name<F: 'static>(
+    _system: &F,
+    base: &'static str,
+    args: impl FnOnce() -> String,
+) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashMap<(TypeId, &'static str), &'static str>>> = OnceLock::new();
+
+    let mut names = NAMES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    names
+        .entry((TypeId::of::<F>(), base))
+        .or_insert_with(|| Box::leak(format!("{base}<{}>", args()).into_boxed_str()))
+}
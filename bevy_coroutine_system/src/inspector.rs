@@ -0,0 +1,89 @@
+//! 运行中协程的运行时信息
+//!
+//! 通过 [`RunningCoroutines::info`](crate::RunningCoroutines::info) 和
+//! [`RunningCoroutines::inspect`](crate::RunningCoroutines::inspect) 查看，用来排查协程为什么还没有结束。
+
+use crate::runtime::CoroutineClock;
+use std::fmt;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 协程 yield 的位置和等待的 Future
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct YieldSite {
+    /// `yield` 在源码中的位置
+    pub location: &'static Location<'static>,
+    /// yield 的 Future 的类型名
+    pub future: &'static str,
+}
+
+impl fmt::Display for YieldSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` at {}", self.future, self.location)
+    }
+}
+
+/// 一个运行中的协程的运行时信息
+///
+/// # Example
+/// ```rust,ignore
+/// fn why_not_finished(running: Res<RunningCoroutines>) {
+///     for (id, info) in running.inspect() {
+///         info!("{id}: {info}");
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CoroutineInfo {
+    /// 协程启动时的帧数，参见 `FrameCount`
+    pub started_frame: u32,
+    /// 协程启动的时间，来自协程的 [`CoroutineClock`]
+    pub started_at: Instant,
+    /// 协程被推进的次数，包括启动时的第一次推进
+    pub resumes: u32,
+    /// 推进协程花费的总时间
+    pub time_spent: Duration,
+    /// 协程执行过的 yield 次数，协程挂起在第 `yields` 个 yield 处
+    pub yields: u32,
+    /// 协程当前挂起的 yield，还没有 yield 过时为 `None`
+    pub waiting_on: Option<YieldSite>,
+    clock: Arc<CoroutineClock>,
+}
+
+impl CoroutineInfo {
+    pub(crate) fn new(started_frame: u32, clock: Arc<CoroutineClock>) -> Self {
+        Self {
+            started_frame,
+            started_at: clock.now(),
+            resumes: 0,
+            time_spent: Duration::ZERO,
+            yields: 0,
+            waiting_on: None,
+            clock,
+        }
+    }
+
+    /// 协程启动后经过的时间，使用手动时钟时只在时钟前进时增加
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.started_at)
+    }
+}
+
+impl fmt::Display for CoroutineInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "started at frame {} ({:.2?} ago), resumed {} times ({:.2?}), {} yields",
+            self.started_frame,
+            self.elapsed(),
+            self.resumes,
+            self.time_spent,
+            self.yields,
+        )?;
+        if let Some(site) = &self.waiting_on {
+            write!(f, ", waiting on {site}")?;
+        }
+        Ok(())
+    }
+}
@@ -9,10 +9,14 @@
 //! - 🔄 **异步操作**: 内置对异步操作的支持（如延时等待）
 //! - 🛠️ **简单易用**: 通过宏自动处理复杂的生命周期和状态管理
 //!
+//! # 后端
+//!
+//! 默认使用 nightly 的协程。启用 `async-backend` feature 后协程体被转换为 async 状态机，
+//! 配合 [`coroutine_systems!`] 可以在 stable Rust 上使用
 //! # 快速开始
 //!
 //! ```rust,ignore
-//! #![feature(coroutines, coroutine_trait)]
+//! #![feature(coroutines)]
 //! 
 //! use bevy::prelude::*;
 //! use bevy_coroutine_system::prelude::*;
@@ -38,20 +42,94 @@
 //! }
 //! ```
 
-#![feature(coroutines, coroutine_trait)]
+#![cfg_attr(not(feature = "async-backend"), feature(coroutine_trait))]
 
 use bevy::prelude::*;
-use bevy::ecs::system::SystemId;
+use bevy::diagnostic::FrameCount;
+use bevy::ecs::component::Tick;
+use bevy::ecs::error::{default_error_handler, ErrorContext};
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
+use bevy::log::tracing::Span;
+use bevy::ecs::system::{
+    SystemChangeTick, SystemId, SystemParam, SystemParamFunction, SystemParamItem,
+};
 use std::any::Any;
-use std::collections::HashMap;
-use std::ops::Coroutine;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
 use std::ptr::NonNull;
 use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+mod backend;
+mod channel;
+mod commands;
+mod config;
+mod diagnostics;
+mod futures;
+mod id;
+mod inspector;
+mod runtime;
+mod task_pool;
+pub mod testing;
+
+use runtime::panic_message;
+
+pub use backend::{BoxedCoroutine, CoroutineStep, ResumeCoroutine};
+#[cfg(feature = "async-backend")]
+pub use backend::{CoroutineYielder, YieldFuture};
+pub use channel::{coroutine_channel, CoroutineReceiver, CoroutineSender, Recv, SendError, TryRecvError};
+pub use commands::{
+    CoroutineCommandsExt, CoroutineEntityCommandsExt, CoroutineSystemFn, CoroutineWorldExt, OwnedCoroutines,
+};
+pub use config::{CoroutineConfig, CoroutineRetrigger, StateScope};
+pub use diagnostics::CoroutineDiagnosticsPlugin;
+pub use id::{generic_coroutine_name, CoroutineId};
+pub use inspector::{CoroutineInfo, YieldSite};
+pub use futures::{
+    into_coroutine_future, next_frame, noop, sleep, typed_coroutine_future, yield_if_over_budget, NextFrame, Noop, Sleep,
+    YieldIfOverBudget, YieldOutput,
+};
+pub use runtime::{CoroutineClock, CoroutineContext, CoroutineWaker, FrameBudget, TimerQueue};
+pub use task_pool::{
+    background_tasks_in_flight, spawn_async_task, spawn_blocking_task, spawn_blocking_task_on, BackgroundTask,
+    BackgroundTaskPool, TaskPanic,
+};
 
 // 重新导出过程宏
 pub use bevy_coroutine_system_macro::*;
 
+/// 协程 yield 出的 Future 经过类型擦除后的类型
+/// 
+/// 参见 [`into_coroutine_future`]
+pub type CoroutineFuture = Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>;
+
+/// 协程执行结束时的结果
+pub type CoroutineResult = Result<(), BevyError>;
+
+/// 可以作为协程系统返回值的类型
+/// 
+/// 协程函数可以返回 `()` 或 `Result<(), E>`，其中 `E` 可以转换为 [`BevyError`]
+pub trait IntoCoroutineResult {
+    /// 转换为 [`CoroutineResult`]
+    fn into_coroutine_result(self) -> CoroutineResult;
+}
+
+impl IntoCoroutineResult for () {
+    fn into_coroutine_result(self) -> CoroutineResult {
+        Ok(())
+    }
+}
+
+impl<E: Into<BevyError>> IntoCoroutineResult for Result<(), E> {
+    fn into_coroutine_result(self) -> CoroutineResult {
+        self.map_err(Into::into)
+    }
+}
 
 /// Bevy 协程系统插件
 /// 
@@ -59,19 +137,50 @@ pub use bevy_coroutine_system_macro::*;
 /// 
 /// # Example
 /// ```rust,ignore
-/// app.add_plugins(CoroutinePlugin);
+/// app.add_plugins(CoroutinePlugin::default());
+///
+/// // 每帧最多花 2 毫秒推进协程
+/// app.add_plugins(CoroutinePlugin {
+///     frame_budget: Some(Duration::from_millis(2)),
+/// });
 /// ```
-pub struct CoroutinePlugin;
+#[derive(Clone, Debug, Default)]
+pub struct CoroutinePlugin {
+    /// 每帧推进协程的时间预算，`None` 表示不限制
+    ///
+    /// 预算用完后协程推进到下一个 yield 就会停下，剩余的工作留到下一帧，参见 [`yield_if_over_budget`]
+    pub frame_budget: Option<Duration>,
+}
 
 impl Plugin for CoroutinePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<RunningCoroutines>()
+            .add_systems(First, begin_frame)
             .add_systems(Update, update_running_tasks);
+        let mut running_task = app.world_mut().resource_mut::<RunningCoroutines>();
+        running_task.driven_schedules.insert(Update.intern());
+        running_task.context.budget.set_limit(self.frame_budget);
     }
 }
 
+/// 每帧开始时重置协程的时间预算，并记录当前帧数
+fn begin_frame(mut running_task: ResMut<RunningCoroutines>, frame_count: Option<Res<FrameCount>>) {
+    running_task.context.budget.reset();
+    running_task.resume_stats = Default::default();
+    running_task.frame = frame_count.map_or(0, |frame_count| frame_count.0);
+}
+
 
 pub trait CoroutineSystem {
+    /// 添加一个协程系统
+    /// 
+    /// 协程的标识符由系统本身得到，不需要单独传入。协程系统第一次通过
+    /// `run_system_cached` 启动时也会自动登记，所以这一步只是提前注册系统
+    /// 
+    /// # 返回值
+    /// 返回注册后的 SystemId
+    fn add_coroutine<M>(&mut self, system: impl IntoSystem<(), (), M> + 'static) -> SystemId;
+
     /// 注册一个协程系统
     /// 
     /// # 参数
@@ -80,78 +189,344 @@ pub trait CoroutineSystem {
     /// 
     /// # 返回值
     /// 返回注册后的 SystemId
-    fn register_coroutine<M>(&mut self, system: impl IntoSystem<(), (), M> + 'static, system_id: &'static str) -> SystemId;
+    #[deprecated(since = "0.1.2", note = "使用 add_coroutine 代替，协程的标识符由系统本身得到")]
+    fn register_coroutine<M>(&mut self, system: impl IntoSystem<(), (), M> + 'static, system_id: CoroutineId) -> SystemId;
 }
 
 impl CoroutineSystem for App {
-    fn register_coroutine<M>(&mut self, system: impl IntoSystem<(), (), M> + 'static, system_id: &'static str) -> SystemId {
-        let id = self.world_mut().register_system_cached(system);
-        self.world_mut().resource_mut::<RunningCoroutines>().register_systems.insert(system_id, id);
-        id
+    fn add_coroutine<M>(&mut self, system: impl IntoSystem<(), (), M> + 'static) -> SystemId {
+        self.world_mut().register_system_cached(system)
+    }
+
+    fn register_coroutine<M>(&mut self, system: impl IntoSystem<(), (), M> + 'static, _system_id: CoroutineId) -> SystemId {
+        self.add_coroutine(system)
     }
 }
 
+/// 重新运行协程系统的函数，由 `#[coroutine_system]` 生成的 `runner()` 提供
+///
+/// 返回协程系统是否运行了，系统参数无效（例如 `Single` 没有匹配的实体）时返回 `false`
+pub type CoroutineRunner = fn(&mut World) -> bool;
+
+
+/// 协程系统运行所需的状态
+/// 
+/// 宏生成的系统把它作为最后一个参数
+#[derive(SystemParam)]
+pub struct CoroutineSystemState<'w, 's> {
+    /// 协程任务
+    pub task: Local<'s, CoroutineTaskSlot>,
+    /// 运行中的协程
+    pub running: ResMut<'w, RunningCoroutines>,
+    /// 系统的变更检测 tick
+    pub ticks: SystemChangeTick,
+}
+
+/// 保存在协程系统的 `Local` 中的协程任务
+/// 
+/// 宏生成的系统按原样声明系统参数，`'static` 的系统参数类型只能在函数体中推导，
+/// 所以任务以擦除类型的形式保存，第一次访问时创建
+#[derive(Default)]
+pub struct CoroutineTaskSlot {
+    task: Option<Box<dyn Any + Send>>,
+}
+
+impl CoroutineTaskSlot {
+    /// 获取协程任务
+    /// 
+    /// `signature` 是参数与协程系统的系统参数相同的函数，用来推导系统参数的类型
+    pub fn task<M, F>(&mut self, _signature: &F) -> &mut CoroutineTask<F::Param>
+    where
+        F: SystemParamFunction<M>,
+        F::Param: 'static,
+    {
+        self.task
+            .get_or_insert_with(|| Box::new(CoroutineTask::<F::Param>::default()))
+            .downcast_mut()
+            .expect("coroutine task slot used with different system params")
+    }
+
+    /// 获取独占 `World` 的协程任务
+    pub fn exclusive_task(&mut self) -> &mut CoroutineTask<ExclusiveWorld> {
+        self.task
+            .get_or_insert_with(|| Box::new(CoroutineTask::<ExclusiveWorld>::default()))
+            .downcast_mut()
+            .expect("coroutine task slot used with different system params")
+    }
+}
+
+/// 独占 `World` 的协程系统运行所需的状态
+/// 
+/// 宏为参数是 `&mut World` 的函数生成的独占系统把它作为最后一个参数
+pub type ExclusiveCoroutineState<'s> = Local<'s, CoroutineTaskSlot>;
+
+/// 将协程系统接收到的参数视为 `signature` 推导出的系统参数
+/// 
+/// 参数中省略的生命周期在系统函数体中是各自独立的，无法直接与 `SystemParamItem<'w, 's, P>` 对应，
+/// 但两者是同一个类型。参数以擦除类型的指针传入：在函数体中以参数类型实例化泛型
+/// 会要求证明其中省略的生命周期满足 `'static` 约束（如 `ParamSet` 中的 `Query<&mut T>`），
+/// 而这只在函数签名中被隐含
+/// 
+/// # Safety
+/// `params` 必须指向 Bevy 为 `signature` 的参数组成的元组获取的值，
+/// 且在返回的生命周期 `'a` 内没有其他对它的访问。`'a` 不能长于获取参数时的 `'w` 和 `'s`，
+/// 通常就是对这个局部元组的借用
+pub unsafe fn signature_params<'a, M, F>(
+    _signature: &F,
+    params: *mut (),
+) -> &'a mut SystemParamItem<'a, 'a, F::Param>
+where
+    F: SystemParamFunction<M>,
+{
+    // SAFETY: 调用者保证指针指向 `SystemParamItem<'w, 's, F::Param>` 类型的有效值，且 `'a` 内没有其他访问。
+    // 只有生命周期参数不同，内存布局相同。`'a` 是调用处对这个局部元组的借用，短于 `'w` 和 `'s`，
+    // 因此把两者统一为 `'a` 只会缩短返回值中的借用，协程在 `'a` 内通过它访问的数据都仍然有效。
+    // 参数类型对生命周期不一定是协变的，通过 `&mut` 写入的值因此也只能来自这些参数本身
+    // （协程输入在每次恢复结束时失效，无法把上一次恢复的借用写入），
+    // 它们实际借用的仍是 `'w` 的 World 和 `'s` 的系统状态，`'a` 结束后原值的析构不会访问到失效的借用
+    unsafe { &mut *params.cast::<SystemParamItem<'a, 'a, F::Param>>() }
+}
+
+
+/// 协程每次恢复时传入的参数
+/// 
+/// 所有的 [`SystemParam`] 都实现了它，独占 `World` 的协程使用 [`ExclusiveWorld`]
+pub trait CoroutineParam: 'static {
+    /// 一次运行中获取到的参数
+    type Item<'w, 's>;
+}
+
+impl<P: SystemParam + 'static> CoroutineParam for P {
+    type Item<'w, 's> = SystemParamItem<'w, 's, P>;
+}
+
+/// 独占 `World` 的协程的参数，每次恢复时传入 `&mut World`
+pub struct ExclusiveWorld;
+
+impl CoroutineParam for ExclusiveWorld {
+    type Item<'w, 's> = (&'w mut World,);
+}
 
 /// 协程任务的容器
-pub struct CoroutineTask<R> {
+/// 
+/// `P` 是协程使用的参数，每次恢复协程时通过 [`CoroutineTaskInput`] 传入
+pub struct CoroutineTask<P> {
     /// 协程实例
-    pub coroutine: Option<
-        Pin<
-            Box<
-                dyn Coroutine<
-                        R,
-                        Yield = Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>,
-                        Return = (),
-                    > + Send,
-            >,
-        >,
-    >,
+    pub coroutine: Option<BoxedCoroutine<CoroutineTaskInput<P>>>,
     /// 当前挂起的Future
-    pub fut: Option<Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>>,
+    pub fut: Option<CoroutineFuture>,
+    /// 协程的唤醒器，挂起的Future完成时通过它通知驱动系统
+    pub waker: Arc<CoroutineWaker>,
+    /// 当前恢复的代数，每次恢复结束后递增，使之前的输入失效
+    generation: Arc<AtomicU64>,
 }
 
-impl<R> Default for CoroutineTask<R> {
+impl<P: CoroutineParam> Default for CoroutineTask<P> {
     fn default() -> Self {
         Self {
             coroutine: None,
             fut: None,
+            waker: CoroutineWaker::new(),
+            generation: Arc::default(),
+        }
+    }
+}
+
+impl<P: CoroutineParam> CoroutineTask<P> {
+    /// 设置要执行的协程
+    /// 
+    /// 协程的返回值可以是 `()` 或 `Result<(), E>`，参见 [`IntoCoroutineResult`]
+    #[cfg(not(feature = "async-backend"))]
+    pub fn start<C>(&mut self, coroutine: C)
+    where
+        C: std::ops::Coroutine<CoroutineTaskInput<P>, Yield = CoroutineFuture> + Send + 'static,
+        C::Return: IntoCoroutineResult,
+    {
+        self.coroutine = Some(Box::pin(backend::CoroutineBody(coroutine)));
+        self.fut = None;
+    }
+
+    /// 设置要执行的协程
+    /// 
+    /// `body` 接收 [`CoroutineYielder`] 并返回协程体的 Future，协程体通过它取得每次恢复的输入并 yield。
+    /// 协程的返回值可以是 `()` 或 `Result<(), E>`，参见 [`IntoCoroutineResult`]
+    #[cfg(feature = "async-backend")]
+    pub fn start<F>(&mut self, body: impl FnOnce(CoroutineYielder<CoroutineTaskInput<P>>) -> F)
+    where
+        F: Future + Send + 'static,
+        F::Output: IntoCoroutineResult,
+    {
+        self.coroutine = Some(Box::pin(backend::AsyncCoroutine::new(body)));
+        self.fut = None;
+    }
+
+    /// 丢弃正在运行的协程和挂起的 Future
+    pub fn reset(&mut self) {
+        self.coroutine = None;
+        self.fut = None;
+    }
+
+    /// 协程输入类型的标记，配合 [`CoroutineTaskInput::typed`] 确定协程闭包的输入类型
+    pub fn input_type(&self) -> PhantomData<fn() -> CoroutineTaskInput<P>> {
+        PhantomData
+    }
+
+    /// 推进协程，直到遇到未完成的 Future 或协程结束
+    ///
+    /// `params` 是本次运行获取到的系统参数，只在本次推进期间可以通过 [`CoroutineTaskInput`] 访问。
+    ///
+    /// # 返回值
+    /// 协程仍在等待时返回 `None`，结束时返回其结果（不存在协程时视为成功结束）
+    pub fn step(&mut self, params: &mut P::Item<'_, '_>) -> Option<CoroutineResult> {
+        // 清除唤醒标记，本次推进期间产生的唤醒会在下一帧生效
+        self.waker.take_woken();
+        let waker = Waker::from(self.waker.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            // 处理异步结果
+            let mut async_result = None;
+
+            if let Some(fut) = &mut self.fut {
+                match fut.as_mut().poll(&mut cx) {
+                    Poll::Ready(v) => {
+                        async_result = Some(v);
+                        self.fut = None;
+                    }
+                    Poll::Pending => {
+                        // async 操作未完成，等待被唤醒
+                        return None;
+                    }
+                }
+            }
+
+            let Some(coroutine) = &mut self.coroutine else {
+                return Some(Ok(()));
+            };
+
+            let input = CoroutineTaskInput {
+                params: NonNull::from(&mut *params).cast(),
+                generation: self.generation.load(AtomicOrdering::Acquire),
+                current_generation: self.generation.clone(),
+                async_result,
+                _marker: PhantomData,
+            };
+            let state = {
+                // 恢复结束后（包括 panic 时）让本次的输入失效
+                let _invalidate = InvalidateOnDrop(&self.generation);
+                coroutine.as_mut().resume(input)
+            };
+
+            match state {
+                CoroutineStep::Yielded(fut) => {
+                    self.fut = Some(fut);
+                    // 这一帧的预算用完时把剩余的工作留到下一帧
+                    if CoroutineContext::is_over_budget() {
+                        waker.wake_by_ref();
+                        return None;
+                    }
+                    // 继续循环，检查新 yield 的 future 是否立即完成
+                }
+                CoroutineStep::Complete(result) => {
+                    self.coroutine = None;
+                    self.fut = None;
+                    return Some(result);
+                }
+            }
         }
     }
 }
 
+/// 被丢弃时递增代数
+struct InvalidateOnDrop<'a>(&'a AtomicU64);
+
+impl Drop for InvalidateOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, AtomicOrdering::AcqRel);
+    }
+}
+
 /// 协程的输入参数
-pub struct CoroutineTaskInput<T> {
-    /// 使用裸指针传递任意类型的数据，避免生命周期限制
-    pub data_ptr: Option<NonNull<T>>,
+/// 
+/// 每次恢复协程时创建，持有本次运行的系统参数。系统参数只能在创建它的那次恢复期间访问：
+/// [`params`](Self::params) 返回的引用借用自输入本身，而宏生成的代码会在每个 yield 处用新的输入替换它，
+/// 因此引用无法跨越 yield；在之后的恢复中访问旧的输入会 panic。
+pub struct CoroutineTaskInput<P> {
+    /// 本次运行的系统参数，实际类型为 `P::Item<'w, 's>`
+    params: NonNull<()>,
+    /// 创建输入时的代数
+    generation: u64,
+    /// 所属协程任务当前的代数
+    current_generation: Arc<AtomicU64>,
     /// 异步操作的结果
     pub async_result: Option<Box<dyn Any + Send>>,
+    _marker: PhantomData<fn() -> P>,
 }
 
-// 手动实现 Debug，避免 NonNull 的限制
-impl<T> std::fmt::Debug for CoroutineTaskInput<T> {
+impl<P: CoroutineParam> std::fmt::Debug for CoroutineTaskInput<P> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CoroutineTaskInput")
-            .field("data_ptr", &self.data_ptr.is_some())
+            .field("valid", &self.is_valid())
             .field("async_result", &self.async_result.is_some())
             .finish()
     }
 }
 
-unsafe impl<T: Send> Send for CoroutineTaskInput<T> {}
+// SAFETY: 输入只保存指向系统参数的指针，参数只在创建输入的那次恢复期间被访问，
+// 这次恢复在运行协程系统的线程上进行（带有 `NonSend` 参数的系统总在主线程运行）。
+// 协程连同输入在两次恢复之间被移动到其他线程后，代数已经改变，访问旧的输入会 panic，
+// 所以参数不会在其他线程上被访问，`P` 本身不需要是 `Send`。这个实现不检查任何约束，由上述约定保证
+unsafe impl<P> Send for CoroutineTaskInput<P> {}
+
+impl<P: CoroutineParam> CoroutineTaskInput<P> {
+    /// 原样返回输入，用于在协程闭包中确定输入的类型
+    /// 
+    /// ```rust,ignore
+    /// let input_type = task.input_type();
+    /// task.start(#[coroutine] move |input| {
+    ///     let mut input = CoroutineTaskInput::typed(input, input_type);
+    ///     // ...
+    /// });
+    /// ```
+    pub fn typed(self, _input_type: PhantomData<fn() -> Self>) -> Self {
+        self
+    }
+
+    /// 输入是否属于当前这次恢复
+    pub fn is_valid(&self) -> bool {
+        self.current_generation.load(AtomicOrdering::Acquire) == self.generation
+    }
 
-impl<T> CoroutineTaskInput<T> {
-    /// 获取数据的可变引用
+    /// 访问本次运行的系统参数
+    /// 
+    /// 参数的生命周期被缩短为对输入的借用，不能保存到 yield 之后
     /// 
-    /// # Safety
-    /// 调用者必须确保裸指针仍然有效
-    pub fn data_mut(&mut self) -> &mut T {
-        self.data_ptr
-            .map(|mut ptr| unsafe { ptr.as_mut() })
-            .expect("TaskInput data_ptr is None")
+    /// # Panics
+    /// 在创建该输入的那次恢复结束后调用会 panic
+    pub fn params(&mut self) -> &mut P::Item<'_, '_> {
+        assert!(
+            self.is_valid(),
+            "coroutine parameters accessed outside of the resume they were passed to"
+        );
+        // SAFETY: 代数一致说明 `CoroutineTask::step` 仍在这次恢复中，指针来自它持有的 `&mut P::Item<'w, 's>`，
+        // 而 `&mut self` 保证同一时间只有一个引用；缩短生命周期后参数无法被带出本次借用
+        unsafe { self.params.cast::<P::Item<'_, '_>>().as_mut() }
+    }
+    
+    /// 取回 yield 的 Future 的输出
+    /// 
+    /// 结果的类型由 [`typed_coroutine_future`] 返回的标记在编译期确定
+    pub fn take_result<R: 'static>(&mut self, _output: YieldOutput<R>) -> R {
+        self.async_result
+            .take()
+            .and_then(|v| v.downcast::<R>().ok().map(|b| *b))
+            .expect("yield result does not match the yielded future's output")
     }
     
     /// 获取异步结果并进行类型转换
     /// 
+    /// 宏生成的代码使用类型安全的 [`take_result`](Self::take_result)
+    /// 
     /// # Panics
     /// 如果类型转换失败会panic
     pub fn result<R: 'static>(&mut self) -> R {
@@ -162,178 +537,448 @@ impl<T> CoroutineTaskInput<T> {
     }
 }
 
+/// 协程最近一次运行的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoroutineStatus {
+    /// 正在运行
+    Running,
+    /// 已成功结束
+    Completed,
+    /// 返回了错误或发生了 panic，包含错误信息
+    Failed(String),
+    /// 被取消
+    Cancelled,
+}
+
+/// 协程系统运行时对协程的处理方式，由 [`RunningCoroutines::trigger`] 决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoroutineTrigger {
+    /// 开始一个新的协程
+    Start,
+    /// 恢复正在运行的协程
+    Resume,
+    /// 不做任何处理
+    Skip,
+}
+
 /// 管理所有运行中的协程任务
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct RunningCoroutines {
-    /// 活跃的协程任务
-    pub systems: HashMap<&'static str, ()>,
-    /// 注册的系统ID
-    pub register_systems: HashMap<&'static str, SystemId>,
+    /// 活跃的协程任务及其唤醒器
+    pub systems: HashMap<CoroutineId, Arc<CoroutineWaker>>,
+    /// 恢复协程时用来重新运行协程系统的函数
+    runners: HashMap<CoroutineId, CoroutineRunner>,
+    /// 协程运行上下文
+    pub context: CoroutineContext,
+    /// 处理协程返回的错误，默认使用 Bevy 的 [`default_error_handler`]
+    pub error_handler: fn(BevyError, ErrorContext),
+    /// 每个协程最近一次运行的状态
+    statuses: HashMap<CoroutineId, CoroutineStatus>,
+    /// 每个协程启动时登记的配置
+    configs: HashMap<CoroutineId, CoroutineConfig>,
+    /// 等待取消的协程
+    cancel_requests: HashSet<CoroutineId>,
+    /// 每个协程第一次登记的顺序，决定优先级相同的协程的恢复顺序
+    registration_order: HashMap<CoroutineId, u64>,
+    /// 运行中协程的运行时信息
+    infos: HashMap<CoroutineId, CoroutineInfo>,
+    /// 拥有协程的实体，按成为拥有者的顺序排列
+    owners: HashMap<CoroutineId, Vec<Entity>>,
+    /// 当前帧数，由 `CoroutinePlugin` 每帧更新
+    frame: u32,
+    /// 这一帧推进协程的统计，由 [`CoroutineDiagnosticsPlugin`] 读取
+    resume_stats: diagnostics::ResumeStats,
+    /// 驱动系统正在恢复的协程
+    driving: Option<CoroutineId>,
+    /// 已经添加了驱动系统的调度
+    driven_schedules: HashSet<InternedScheduleLabel>,
+    /// 等待添加驱动系统的调度
+    pending_schedules: Vec<InternedScheduleLabel>,
 }
 
-fn update_running_tasks(mut commands: Commands, running_task: Res<RunningCoroutines>) {
-    if running_task.systems.is_empty() {
-        return;
-    }
-    for (system_name, system_id) in running_task.register_systems.iter() {
-        if running_task.systems.contains_key(system_name) {
-            commands.run_system(*system_id);
+impl Default for RunningCoroutines {
+    fn default() -> Self {
+        Self {
+            systems: HashMap::new(),
+            runners: HashMap::new(),
+            context: CoroutineContext::default(),
+            error_handler: default_error_handler(),
+            statuses: HashMap::new(),
+            configs: HashMap::new(),
+            cancel_requests: HashSet::new(),
+            registration_order: HashMap::new(),
+            infos: HashMap::new(),
+            owners: HashMap::new(),
+            frame: 0,
+            resume_stats: Default::default(),
+            driving: None,
+            driven_schedules: HashSet::new(),
+            pending_schedules: Vec::new(),
         }
     }
 }
 
-/// 创建一个睡眠Future
-/// 
-/// # Example
-/// ```rust,ignore
-/// yield sleep(Duration::from_secs(1));
-/// ```
-pub fn sleep(duration: std::time::Duration) -> Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>> {
-    use std::time::Instant;
-    
-    struct SleepFuture {
-        target_time: Instant,
-    }
-    
-    impl Future for SleepFuture {
-        type Output = Box<dyn Any + Send>;
-        
-        fn poll(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-            if Instant::now() >= self.target_time {
-                std::task::Poll::Ready(Box::new(Instant::now()) as Box<dyn Any + Send>)
-            } else {
-                std::task::Poll::Pending
+impl RunningCoroutines {
+    /// 决定协程系统这次运行时如何处理协程
+    /// 
+    /// 驱动系统恢复被唤醒的协程时返回 [`CoroutineTrigger::Resume`]；
+    /// 协程运行中被再次触发时按 [`CoroutineConfig::retrigger`] 处理；等待取消的协程会在这里被取消
+    pub fn trigger<P: CoroutineParam>(
+        &mut self,
+        system_id: CoroutineId,
+        task: &mut CoroutineTask<P>,
+    ) -> CoroutineTrigger {
+        // 驱动系统只恢复启动协程的那个系统实例，同一函数作为常规系统添加的其他实例由它们自己推进
+        if self.driving == Some(system_id)
+            && !self
+                .systems
+                .get(&system_id)
+                .is_some_and(|waker| Arc::ptr_eq(waker, &task.waker))
+        {
+            return CoroutineTrigger::Skip;
+        }
+        if self.cancel_requests.remove(&system_id) {
+            task.reset();
+            self.forget(system_id);
+            self.statuses.insert(system_id, CoroutineStatus::Cancelled);
+            return CoroutineTrigger::Skip;
+        }
+        // 协程在系统无法运行时被驱动系统取消，丢弃残留的协程状态
+        if task.coroutine.is_some() && !self.systems.contains_key(&system_id) {
+            task.reset();
+        }
+        if task.coroutine.is_none() {
+            return CoroutineTrigger::Start;
+        }
+        if self.driving == Some(system_id) {
+            return CoroutineTrigger::Resume;
+        }
+        let retrigger = self
+            .configs
+            .get(&system_id)
+            .map(|config| config.retrigger)
+            .unwrap_or_default();
+        match retrigger {
+            CoroutineRetrigger::Resume => CoroutineTrigger::Resume,
+            CoroutineRetrigger::Ignore => CoroutineTrigger::Skip,
+            CoroutineRetrigger::Restart => {
+                task.reset();
+                CoroutineTrigger::Start
             }
         }
     }
-    
-    Box::pin(SleepFuture {
-        target_time: Instant::now() + duration,
-    })
-}
 
-/// 创建一个等待下一帧的Future
-/// 
-/// 第一次poll时返回Pending，第二次poll时返回Ready
-/// 
-/// # Example
-/// ```rust,ignore
-/// yield next_frame();
-/// ```
-pub fn next_frame() -> Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>> {
-    struct NextFrameFuture {
-        first_poll: bool,
+    /// 将协程标记为活跃，并登记它的配置和重新运行协程系统的函数
+    pub fn insert<P: CoroutineParam>(
+        &mut self,
+        system_id: CoroutineId,
+        task: &CoroutineTask<P>,
+        config: CoroutineConfig,
+        runner: CoroutineRunner,
+    ) {
+        self.systems.insert(system_id, task.waker.clone());
+        self.runners.insert(system_id, runner);
+        let next_order = self.registration_order.len() as u64;
+        self.registration_order.entry(system_id).or_insert(next_order);
+        self.infos.insert(system_id, CoroutineInfo::new(self.frame, self.context.clock.clone()));
+        self.statuses.insert(system_id, CoroutineStatus::Running);
+        if !self.driven_schedules.contains(&config.schedule)
+            && !self.pending_schedules.contains(&config.schedule)
+        {
+            self.pending_schedules.push(config.schedule);
+        }
+        self.configs.insert(system_id, config);
     }
-    
-    impl Future for NextFrameFuture {
-        type Output = Box<dyn Any + Send>;
-        
-        fn poll(mut self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-            if self.first_poll {
-                self.first_poll = false;
-                std::task::Poll::Pending
-            } else {
-                std::task::Poll::Ready(Box::new(()) as Box<dyn Any + Send>)
-            }
+
+    /// 取消正在运行的协程
+    /// 
+    /// 协程会在驱动系统下一次运行时被丢弃，状态变为 [`CoroutineStatus::Cancelled`]
+    pub fn cancel(&mut self, system_id: CoroutineId) {
+        if let Some(waker) = self.systems.get(&system_id) {
+            self.cancel_requests.insert(system_id);
+            Wake::wake_by_ref(waker);
         }
     }
-    
-    Box::pin(NextFrameFuture {
-        first_poll: true,
-    })
-}
 
-/// 创建一个空操作（no-op）的 Future
-/// 
-/// 这个函数立即返回，不执行任何操作。主要用于在协程中创建一个 yield 点，
-/// 帮助解决借用检查问题
-/// 
-/// # Example
-/// ```rust,ignore
-/// // 在两个可能有借用冲突的代码块之间使用
-/// yield noop();
-/// ```
-pub fn noop() -> Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>> {
-    struct NoopFuture;
-    
-    impl Future for NoopFuture {
-        type Output = Box<dyn Any + Send>;
-        
-        fn poll(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-            std::task::Poll::Ready(Box::new(()) as Box<dyn Any + Send>)
+    /// 获取协程启动时登记的配置
+    pub fn config(&self, system_id: CoroutineId) -> Option<&CoroutineConfig> {
+        self.configs.get(&system_id)
+    }
+
+    /// 带有指定标签的活跃协程，按恢复顺序排列
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = CoroutineId> + 'a {
+        let mut tagged: Vec<_> = self
+            .systems
+            .keys()
+            .copied()
+            .filter(|system_id| {
+                self.configs
+                    .get(system_id)
+                    .is_some_and(|config| config.tags.contains(&tag))
+            })
+            .collect();
+        tagged.sort_by_key(|system_id| self.run_order(*system_id));
+        tagged.into_iter()
+    }
+
+    /// 协程的恢复顺序：优先级高的在前，优先级相同时先登记的在前
+    fn run_order(&self, system_id: CoroutineId) -> (std::cmp::Reverse<i32>, u64) {
+        let priority = self.configs.get(&system_id).map_or(0, |config| config.priority);
+        let order = self.registration_order.get(&system_id).copied().unwrap_or(u64::MAX);
+        (std::cmp::Reverse(priority), order)
+    }
+
+    /// 拥有协程的实体，参见 [`CoroutineEntityCommandsExt::start_coroutine`]
+    pub fn owners(&self, system_id: CoroutineId) -> &[Entity] {
+        self.owners.get(&system_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// 让 `entity` 成为协程的拥有者之一
+    pub(crate) fn add_owner(&mut self, system_id: CoroutineId, entity: Entity) {
+        let owners = self.owners.entry(system_id).or_default();
+        if !owners.contains(&entity) {
+            owners.push(entity);
         }
     }
-    
-    Box::pin(NoopFuture)
-}
 
+    /// `entity` 不再拥有协程，最后一个拥有者离开时取消协程
+    pub(crate) fn release_owner(&mut self, system_id: CoroutineId, entity: Entity) {
+        let Some(owners) = self.owners.get_mut(&system_id) else {
+            return;
+        };
+        owners.retain(|owner| *owner != entity);
+        if owners.is_empty() {
+            self.owners.remove(&system_id);
+            self.cancel(system_id);
+        }
+    }
 
-/// 一个通用的Future，用于在后台线程中执行阻塞任务
-struct ThreadFuture<T> {
-    handle: Option<std::thread::JoinHandle<T>>,
-}
+    /// 协程系统被唤醒后因参数无效没有运行
+    ///
+    /// 等待中的取消直接生效，否则保留唤醒标记，下一帧再尝试恢复
+    fn retry(&mut self, system_id: CoroutineId) {
+        if self.cancel_requests.remove(&system_id) {
+            self.forget(system_id);
+            self.statuses.insert(system_id, CoroutineStatus::Cancelled);
+        } else if let Some(waker) = self.systems.get(&system_id) {
+            Wake::wake_by_ref(waker);
+        }
+    }
 
-impl<T: Send + 'static> Future for ThreadFuture<T> {
-    type Output = T;
-    
-    fn poll(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        let this = self.get_mut();
-        if let Some(handle) = &this.handle {
-            if handle.is_finished() {
-                // 线程完成，获取结果
-                let handle = this.handle.take().unwrap();
-                match handle.join() {
-                    Ok(result) => std::task::Poll::Ready(result),
-                    Err(_) => panic!("Thread panicked"),
-                }
-            } else {
-                // 线程还在运行
-                std::task::Poll::Pending
+    /// 清理已结束的协程的状态
+    fn forget(&mut self, system_id: CoroutineId) {
+        // 协程不会再等待它登记的定时器
+        if let Some(waker) = self.systems.remove(&system_id) {
+            self.context.timers.cancel(&Waker::from(waker));
+        }
+        self.infos.remove(&system_id);
+        self.owners.remove(&system_id);
+    }
+
+    /// 将协程标记为已结束
+    pub fn remove(&mut self, system_id: CoroutineId) {
+        self.forget(system_id);
+        self.statuses.insert(system_id, CoroutineStatus::Completed);
+    }
+
+    /// 记录协程结束的结果
+    /// 
+    /// 失败时错误会交给 [`error_handler`](Self::error_handler) 处理
+    pub fn complete(&mut self, system_id: CoroutineId, result: CoroutineResult, last_run: Tick) {
+        // 自动重复的协程保持活跃，下一帧由驱动系统重新开始
+        if result.is_ok() && self.configs.get(&system_id).is_some_and(|config| config.repeat) {
+            if let Some(waker) = self.systems.get(&system_id) {
+                Wake::wake_by_ref(waker);
+            }
+            return;
+        }
+        self.remove(system_id);
+        if let Err(error) = result {
+            // BevyError 的 Display 可能附带 backtrace，只保留错误信息
+            let message = error.to_string().lines().next().unwrap_or_default().to_owned();
+            self.statuses.insert(system_id, CoroutineStatus::Failed(message));
+            (self.error_handler)(
+                error,
+                ErrorContext::System {
+                    name: system_id.name().into(),
+                    last_run,
+                },
+            );
+        }
+    }
+
+    /// 获取协程最近一次运行的状态，从未运行过时返回 `None`
+    pub fn status(&self, system_id: CoroutineId) -> Option<&CoroutineStatus> {
+        self.statuses.get(&system_id)
+    }
+
+    /// 在运行上下文中推进协程，并记录协程结束的结果
+    ///
+    /// 协程 panic 时会被捕获：输出带有协程 ID 的错误日志，将协程标记为失败并清理其状态，
+    /// 不会影响其他系统
+    pub fn resume<P: CoroutineParam>(
+        &mut self,
+        system_id: CoroutineId,
+        task: &mut CoroutineTask<P>,
+        last_run: Tick,
+        params: &mut P::Item<'_, '_>,
+    ) {
+        let _span = self.resume_span(system_id).entered();
+        let started = Instant::now();
+        let outcome = step_in_context(&self.context, task, params);
+        self.record_step(system_id, started.elapsed());
+        self.finish(system_id, task, last_run, outcome);
+    }
+
+    /// 推进独占 `World` 的协程，并记录协程结束的结果
+    ///
+    /// 推进期间不会借用 `RunningCoroutines`，协程体可以使用 `World` 中的任何数据，包括运行调度。
+    /// `last_run` 是协程系统上一次运行的 tick
+    pub fn resume_exclusive(
+        world: &mut World,
+        system_id: CoroutineId,
+        task: &mut CoroutineTask<ExclusiveWorld>,
+        last_run: Tick,
+    ) {
+        let running_task = world.resource::<RunningCoroutines>();
+        let context = running_task.context.clone();
+        let _span = running_task.resume_span(system_id).entered();
+        let started = Instant::now();
+        let outcome = step_in_context(&context, task, &mut (&mut *world,));
+        let mut running_task = world.resource_mut::<RunningCoroutines>();
+        running_task.record_step(system_id, started.elapsed());
+        running_task.finish(system_id, task, last_run, outcome);
+    }
+
+    /// 获取运行中协程的运行时信息
+    pub fn info(&self, system_id: CoroutineId) -> Option<&CoroutineInfo> {
+        self.infos.get(&system_id)
+    }
+
+    /// 所有运行中协程的运行时信息，按恢复顺序排列
+    pub fn inspect(&self) -> impl Iterator<Item = (CoroutineId, &CoroutineInfo)> {
+        let mut infos: Vec<_> = self.infos.iter().map(|(system_id, info)| (*system_id, info)).collect();
+        infos.sort_by_key(|(system_id, _)| self.run_order(*system_id));
+        infos.into_iter()
+    }
+
+    /// 推进协程时进入的 tracing span，带有协程的名称和恢复协程的 yield
+    fn resume_span(&self, system_id: CoroutineId) -> Span {
+        match self.infos.get(&system_id).and_then(|info| info.waiting_on) {
+            Some(site) => info_span!("coroutine", name = system_id.name(), yield_point = %site),
+            None => info_span!("coroutine", name = system_id.name(), yield_point = "start"),
+        }
+    }
+
+    /// 把一次推进计入协程的运行时信息
+    fn record_step(&mut self, system_id: CoroutineId, elapsed: Duration) {
+        let yields = self.context.take_yields();
+        self.resume_stats.record(elapsed);
+        if let Some(info) = self.infos.get_mut(&system_id) {
+            info.resumes += 1;
+            info.time_spent += elapsed;
+            info.yields += yields.count;
+            if yields.last.is_some() {
+                info.waiting_on = yields.last;
+            }
+        }
+    }
+
+    /// 记录协程一次推进的结果
+    fn finish<P: CoroutineParam>(
+        &mut self,
+        system_id: CoroutineId,
+        task: &mut CoroutineTask<P>,
+        last_run: Tick,
+        outcome: std::thread::Result<Option<CoroutineResult>>,
+    ) {
+        match outcome {
+            Ok(None) => {}
+            Ok(Some(result)) => self.complete(system_id, result, last_run),
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                error!("Coroutine `{system_id}` panicked: {message}");
+                // 协程的状态已不可用，下次运行时重新开始
+                task.reset();
+                self.forget(system_id);
+                self.statuses
+                    .insert(system_id, CoroutineStatus::Failed(format!("panicked: {message}")));
             }
-        } else {
-            // handle已经被取走，这不应该发生
-            panic!("ThreadFuture polled after completion");
         }
     }
 }
 
-/// 一个包装Future，用于将输出类型转换为Box<dyn Any + Send>
-struct AnyFuture<T> {
-    inner: ThreadFuture<T>,
+/// 在运行上下文中推进协程，并捕获协程中的 panic
+fn step_in_context<P: CoroutineParam>(
+    context: &CoroutineContext,
+    task: &mut CoroutineTask<P>,
+    params: &mut P::Item<'_, '_>,
+) -> std::thread::Result<Option<CoroutineResult>> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        context.budget.measure(|| context.scope(|| task.step(params)))
+    }))
 }
 
-impl<T: Send + Any + 'static> Future for AnyFuture<T> {
-    type Output = Box<dyn Any + Send>;
-    
-    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        match Pin::new(&mut self.inner).poll(cx) {
-            std::task::Poll::Ready(value) => std::task::Poll::Ready(Box::new(value) as Box<dyn Any + Send>),
-            std::task::Poll::Pending => std::task::Poll::Pending,
+/// 驱动 `Update` 中被唤醒的协程
+fn update_running_tasks(world: &mut World) {
+    drive_coroutines(world, Update.intern());
+}
+
+/// 驱动在 `schedule` 中运行的被唤醒的协程
+///
+/// 到期的定时器会先被触发，随后只有被唤醒的协程会重新运行，按优先级从高到低、登记顺序从早到晚恢复；
+/// 离开所属状态的协程会被取消。系统参数无效而没能运行的协程在下一帧重试
+fn drive_coroutines(world: &mut World, schedule: InternedScheduleLabel) {
+    // 为协程配置中新出现的调度添加驱动系统
+    let pending = std::mem::take(&mut world.resource_mut::<RunningCoroutines>().pending_schedules);
+    for label in pending {
+        if world.resource_mut::<RunningCoroutines>().driven_schedules.insert(label) {
+            world
+                .resource_mut::<Schedules>()
+                .add_systems(label, move |world: &mut World| drive_coroutines(world, label));
         }
     }
-}
 
-/// 一个通用的函数，用于在后台线程中执行阻塞任务并返回一个Future
-/// 
-/// # Example
-/// ```rust,ignore
-/// let result: String = yield spawn_blocking_task(move || {
-///     // 阻塞任务
-///     // ...
-///     return "result";
-/// });
-pub fn spawn_blocking_task<F, T>(task: F) -> Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>
-where
-    F: FnOnce() -> T + Send + 'static,
-    T: Send + Any + 'static,
-{
-    let handle = std::thread::spawn(task);
-    
-    Box::pin(AnyFuture {
-        inner: ThreadFuture {
-            handle: Some(handle),
+    let (woken, out_of_scope) = {
+        let running_task = world.resource::<RunningCoroutines>();
+        if running_task.systems.is_empty() {
+            return;
+        }
+        running_task.context.timers.fire(running_task.context.clock.now());
+        let mut woken = Vec::new();
+        let mut out_of_scope = Vec::new();
+        for (system_id, waker) in &running_task.systems {
+            let Some(runner) = running_task.runners.get(system_id) else {
+                continue;
+            };
+            let config = running_task.configs.get(system_id);
+            if config.map_or(Update.intern(), |config| config.schedule) != schedule {
+                continue;
+            }
+            if config.is_some_and(|config| !config.in_scope(world)) {
+                out_of_scope.push(*system_id);
+                woken.push((*system_id, *runner));
+            } else if waker.take_woken() {
+                woken.push((*system_id, *runner));
+            }
+        }
+        // 按确定的顺序恢复，保证每次运行的行为一致
+        woken.sort_by_key(|(system_id, _)| running_task.run_order(*system_id));
+        (woken, out_of_scope)
+    };
+    let mut running_task = world.resource_mut::<RunningCoroutines>();
+    for system_id in out_of_scope {
+        running_task.cancel(system_id);
+    }
+    for (system_id, runner) in woken {
+        world.resource_mut::<RunningCoroutines>().driving = Some(system_id);
+        let ran = runner(world);
+        let mut running_task = world.resource_mut::<RunningCoroutines>();
+        running_task.driving = None;
+        if !ran {
+            running_task.retry(system_id);
         }
-    })
+    }
 }
 
 /// yield_async!宏（已废弃，推荐使用原生 yield 语法）
@@ -363,22 +1008,42 @@ pub mod prelude {
     pub use crate::{
         // Trait
         CoroutineSystem,
+        CoroutineCommandsExt,
+        CoroutineWorldExt,
+        CoroutineEntityCommandsExt,
         
         // 宏（从 bevy_coroutine_system_macro 重新导出）
         coroutine_system,
+        coroutine_systems,
         
         // 插件
         CoroutinePlugin,
+        CoroutineDiagnosticsPlugin,
         
         // 函数
         sleep,
         next_frame,
         noop,
+        yield_if_over_budget,
         spawn_blocking_task,
+        spawn_blocking_task_on,
+        spawn_async_task,
+        coroutine_channel,
         
         // 类型
+        BackgroundTaskPool,
+        CoroutineConfig,
+        CoroutineId,
+        CoroutineReceiver,
+        CoroutineSender,
+        CoroutineRetrigger,
+        CoroutineStatus,
+        CoroutineSystemState,
+        ExclusiveCoroutineState,
         CoroutineTask,
         CoroutineTaskInput,
+        CoroutineTaskSlot,
         RunningCoroutines,
+        TaskPanic,
     };
 }
@@ -0,0 +1,380 @@
+//! 协程运行时：唤醒器、定时器与驱动上下文
+//!
+//! 协程挂起的 Future 不再每帧被轮询，而是在被唤醒后才会由驱动系统重新运行。
+
+use crate::YieldSite;
+use std::any::Any;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::task::{Wake, Waker};
+use std::time::{Duration, Instant};
+
+/// 协程的唤醒器
+///
+/// 挂起的 Future 完成时通过它标记协程需要被恢复，驱动系统只会运行被唤醒的协程。
+#[derive(Debug)]
+pub struct CoroutineWaker {
+    woken: AtomicBool,
+}
+
+impl CoroutineWaker {
+    /// 创建一个未被唤醒的唤醒器
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            woken: AtomicBool::new(false),
+        })
+    }
+
+    /// 是否已被唤醒
+    pub fn is_woken(&self) -> bool {
+        self.woken.load(AtomicOrdering::Acquire)
+    }
+
+    /// 取出唤醒标记，并将其重置为未唤醒
+    pub fn take_woken(&self) -> bool {
+        self.woken.swap(false, AtomicOrdering::AcqRel)
+    }
+}
+
+impl Wake for CoroutineWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, AtomicOrdering::Release);
+    }
+}
+
+/// 定时器队列中的一项
+struct TimerEntry {
+    deadline: Instant,
+    waker: Waker,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap 是大顶堆，反转顺序让最早到期的定时器位于堆顶
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+/// 定时器队列
+///
+/// `sleep` 在挂起时把唤醒器登记到这里，驱动系统每帧唤醒所有到期的定时器。
+#[derive(Default)]
+pub struct TimerQueue {
+    timers: Mutex<BinaryHeap<TimerEntry>>,
+}
+
+impl TimerQueue {
+    /// 登记一个在 `deadline` 时刻唤醒的定时器
+    ///
+    /// 同一个 `sleep` 在到期前可能被多次轮询，已经登记过的定时器不会重复登记
+    pub fn register(&self, deadline: Instant, waker: Waker) {
+        let mut timers = self.timers.lock().unwrap();
+        if timers
+            .iter()
+            .any(|entry| entry.deadline == deadline && entry.waker.will_wake(&waker))
+        {
+            return;
+        }
+        timers.push(TimerEntry { deadline, waker });
+    }
+
+    /// 移除唤醒 `waker` 的所有定时器，在协程结束或被取消时调用
+    pub fn cancel(&self, waker: &Waker) {
+        self.timers.lock().unwrap().retain(|entry| !entry.waker.will_wake(waker));
+    }
+
+    /// 唤醒所有在 `now` 之前到期的定时器
+    pub fn fire(&self, now: Instant) {
+        let mut timers = self.timers.lock().unwrap();
+        while timers.peek().is_some_and(|entry| entry.deadline <= now) {
+            if let Some(entry) = timers.pop() {
+                entry.waker.wake();
+            }
+        }
+    }
+
+    /// 未到期的定时器数量
+    pub fn len(&self) -> usize {
+        self.timers.lock().unwrap().len()
+    }
+
+    /// 是否没有未到期的定时器
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl std::fmt::Debug for TimerQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimerQueue")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// 每帧协程工作的时间预算
+///
+/// 记录这一帧推进协程花费的时间，超出上限后协程推进到下一个 yield 就会停下，
+/// 剩余的工作留到下一帧。预算在每帧开始时由 `CoroutinePlugin` 重置。
+/// 时间由协程的 [`CoroutineClock`] 测量，使用手动时钟时只有时钟前进的时间计入预算
+#[derive(Debug, Default)]
+pub struct FrameBudget {
+    state: Mutex<BudgetState>,
+    clock: Arc<CoroutineClock>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    limit: Option<Duration>,
+    spent: Duration,
+    /// 最外层的推进开始的时间
+    running_since: Option<Instant>,
+}
+
+impl FrameBudget {
+    /// 创建使用 `clock` 测量时间的预算
+    pub(crate) fn with_clock(clock: Arc<CoroutineClock>) -> Self {
+        Self {
+            state: Mutex::default(),
+            clock,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BudgetState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 每帧的预算上限，`None` 表示不限制
+    pub fn limit(&self) -> Option<Duration> {
+        self.state().limit
+    }
+
+    /// 设置每帧的预算上限
+    pub fn set_limit(&self, limit: Option<Duration>) {
+        self.state().limit = limit;
+    }
+
+    /// 这一帧推进协程已经花费的时间，包括正在进行的推进
+    pub fn spent(&self) -> Duration {
+        let state = self.state();
+        let running = state
+            .running_since
+            .map_or(Duration::ZERO, |since| self.clock.now().saturating_duration_since(since));
+        state.spent + running
+    }
+
+    /// 这一帧的预算是否已经用完
+    pub fn is_exhausted(&self) -> bool {
+        self.limit().is_some_and(|limit| self.spent() >= limit)
+    }
+
+    /// 开始新的一帧
+    pub fn reset(&self) {
+        self.state().spent = Duration::ZERO;
+    }
+
+    /// 执行 `f` 并把花费的时间计入预算
+    ///
+    /// 嵌套的推进（如独占协程运行的调度中驱动的协程）只由最外层计时
+    pub(crate) fn measure<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Stop<'a>(Option<&'a FrameBudget>);
+
+        impl Drop for Stop<'_> {
+            fn drop(&mut self) {
+                if let Some(budget) = self.0 {
+                    let mut state = budget.state();
+                    if let Some(since) = state.running_since.take() {
+                        state.spent += budget.clock.now().saturating_duration_since(since);
+                    }
+                }
+            }
+        }
+
+        let outermost = {
+            let mut state = self.state();
+            let outermost = state.running_since.is_none();
+            if outermost {
+                state.running_since = Some(self.clock.now());
+            }
+            outermost
+        };
+        let _stop = Stop(outermost.then_some(self));
+        f()
+    }
+}
+
+/// 协程使用的时钟
+///
+/// 默认使用系统时间；切换为手动时钟后只在 [`advance`](Self::advance) 时前进，`sleep` 和定时器都以它为准，
+/// 用于在测试中精确控制时间，参见 [`CoroutineTestApp`](crate::testing::CoroutineTestApp)
+#[derive(Debug, Default)]
+pub struct CoroutineClock {
+    manual: Mutex<Option<Instant>>,
+}
+
+impl CoroutineClock {
+    fn manual(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.manual.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 当前时间
+    pub fn now(&self) -> Instant {
+        self.manual().unwrap_or_else(Instant::now)
+    }
+
+    /// 是否为手动时钟
+    pub fn is_manual(&self) -> bool {
+        self.manual().is_some()
+    }
+
+    /// 切换为手动时钟，时间停在当前时刻
+    pub fn set_manual(&self) {
+        let mut manual = self.manual();
+        if manual.is_none() {
+            *manual = Some(Instant::now());
+        }
+    }
+
+    /// 让手动时钟前进 `duration`，不是手动时钟时先切换为手动时钟
+    pub fn advance(&self, duration: Duration) {
+        let mut manual = self.manual();
+        *manual = Some(manual.unwrap_or_else(Instant::now) + duration);
+    }
+}
+
+/// 协程 yield 的记录
+#[derive(Debug, Default)]
+pub(crate) struct YieldRecord {
+    /// 推进期间执行的 yield 次数
+    pub count: u32,
+    /// 最近一次 yield 的位置
+    pub last: Option<YieldSite>,
+}
+
+/// 协程运行上下文
+///
+/// 在恢复协程和轮询 Future 期间被设置为当前线程的上下文，
+/// 内置的 Future（如 `sleep`）通过它访问运行时。
+#[derive(Clone, Debug)]
+pub struct CoroutineContext {
+    pub(crate) timers: Arc<TimerQueue>,
+    pub(crate) budget: Arc<FrameBudget>,
+    pub(crate) clock: Arc<CoroutineClock>,
+    /// 推进期间的 yield，推进结束后记录到协程的运行时信息中
+    pub(crate) yields: Arc<Mutex<YieldRecord>>,
+}
+
+impl Default for CoroutineContext {
+    fn default() -> Self {
+        // 预算和定时器使用同一个时钟
+        let clock = Arc::<CoroutineClock>::default();
+        Self {
+            timers: Arc::default(),
+            budget: Arc::new(FrameBudget::with_clock(clock.clone())),
+            clock,
+            yields: Arc::default(),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT_CONTEXT: RefCell<Option<CoroutineContext>> = const { RefCell::new(None) };
+}
+
+impl CoroutineContext {
+    /// 在该上下文中执行 `f`
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<CoroutineContext>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CURRENT_CONTEXT.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+
+        let previous = CURRENT_CONTEXT.with(|current| current.borrow_mut().replace(self.clone()));
+        let _restore = Restore(previous);
+        f()
+    }
+
+    /// 未到期的定时器
+    pub fn timers(&self) -> &TimerQueue {
+        &self.timers
+    }
+
+    /// 每帧协程工作的时间预算
+    pub fn budget(&self) -> &FrameBudget {
+        &self.budget
+    }
+
+    /// 当前线程的协程上下文的预算是否已经用完，不在协程中时返回 `false`
+    pub fn is_over_budget() -> bool {
+        Self::with_current(|context| context.is_some_and(|context| context.budget.is_exhausted()))
+    }
+
+    /// 协程使用的时钟
+    pub fn clock(&self) -> &CoroutineClock {
+        &self.clock
+    }
+
+    /// 当前线程的协程上下文的时钟的当前时间，不在协程中时使用系统时间
+    pub fn now() -> Instant {
+        Self::with_current(|context| context.map_or_else(Instant::now, |context| context.clock.now()))
+    }
+
+    /// 记录当前线程的协程 yield 的位置，不在协程中时什么也不做
+    pub(crate) fn record_yield(site: YieldSite) {
+        Self::with_current(|context| {
+            if let Some(context) = context {
+                let mut yields = context.yields.lock().unwrap_or_else(|e| e.into_inner());
+                yields.count += 1;
+                yields.last = Some(site);
+            }
+        });
+    }
+
+    /// 取出推进期间的 yield 记录
+    pub(crate) fn take_yields(&self) -> YieldRecord {
+        std::mem::take(&mut *self.yields.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// 访问当前线程的协程上下文
+    ///
+    /// 不在协程中时传入 `None`
+    pub fn with_current<T>(f: impl FnOnce(Option<&CoroutineContext>) -> T) -> T {
+        CURRENT_CONTEXT.with(|current| f(current.borrow().as_ref()))
+    }
+}
+
+/// 从 panic 的负载中取出 panic 信息
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
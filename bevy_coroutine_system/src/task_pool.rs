@@ -0,0 +1,296 @@
+//! 在 Bevy 任务池上执行的后台任务
+//!
+//! 阻塞任务和异步任务不再各自创建线程，而是交给 `AsyncComputeTaskPool` 或 `IoTaskPool` 执行，
+//! 任务完成时唤醒等待它的协程。
+
+use crate::runtime::panic_message;
+use bevy::tasks::futures_lite::FutureExt;
+use bevy::tasks::{AsyncComputeTaskPool, IoTaskPool, Task, TaskPool};
+use bevy::utils::synccell::SyncCell;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+
+/// 后台任务使用的 Bevy 任务池
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BackgroundTaskPool {
+    /// `AsyncComputeTaskPool`，适合耗时的计算任务
+    #[default]
+    AsyncCompute,
+    /// `IoTaskPool`，适合文件、网络等 IO 任务
+    Io,
+}
+
+impl BackgroundTaskPool {
+    /// 获取对应的任务池，未初始化时使用默认配置创建
+    pub fn task_pool(self) -> &'static TaskPool {
+        match self {
+            Self::AsyncCompute => AsyncComputeTaskPool::get_or_init(TaskPool::default),
+            Self::Io => IoTaskPool::get_or_init(TaskPool::default),
+        }
+    }
+
+    /// 该任务池上同时执行的阻塞任务的最大数量
+    ///
+    /// 默认等于任务池的线程数，超出的任务会排队等待
+    pub fn max_blocking_tasks(self) -> usize {
+        self.limit().max(self)
+    }
+
+    /// 设置该任务池上同时执行的阻塞任务的最大数量
+    ///
+    /// 传入 `0` 会恢复为默认值
+    pub fn set_max_blocking_tasks(self, max: usize) {
+        self.limit().set_max(max);
+    }
+
+    fn limit(self) -> &'static ConcurrencyLimit {
+        static ASYNC_COMPUTE: ConcurrencyLimit = ConcurrencyLimit::new();
+        static IO: ConcurrencyLimit = ConcurrencyLimit::new();
+
+        match self {
+            Self::AsyncCompute => &ASYNC_COMPUTE,
+            Self::Io => &IO,
+        }
+    }
+}
+
+/// 限制同时执行的阻塞任务数量
+struct ConcurrencyLimit {
+    state: Mutex<LimitState>,
+}
+
+struct LimitState {
+    /// 最大并发数，`0` 表示使用任务池的线程数
+    max: usize,
+    /// 正在执行的任务数
+    running: usize,
+    /// 等待执行的任务
+    waiters: VecDeque<Waker>,
+}
+
+impl ConcurrencyLimit {
+    const fn new() -> Self {
+        Self {
+            state: Mutex::new(LimitState {
+                max: 0,
+                running: 0,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn max(&self, pool: BackgroundTaskPool) -> usize {
+        let max = self.state.lock().unwrap_or_else(|e| e.into_inner()).max;
+        if max == 0 {
+            pool.task_pool().thread_num().max(1)
+        } else {
+            max
+        }
+    }
+
+    fn set_max(&self, max: usize) {
+        let waiters = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.max = max;
+            std::mem::take(&mut state.waiters)
+        };
+        // 上限可能变大，让等待的任务重新尝试
+        waiters.into_iter().for_each(Waker::wake);
+    }
+
+    /// 等待一个执行许可
+    async fn acquire(&'static self, pool: BackgroundTaskPool) -> Permit {
+        let max = self.max(pool);
+        std::future::poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let max = if state.max == 0 { max } else { state.max };
+            if state.running < max {
+                state.running += 1;
+                Poll::Ready(Permit(self))
+            } else {
+                state.waiters.push_back(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// 执行许可，释放时唤醒等待的任务
+struct Permit(&'static ConcurrencyLimit);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.running -= 1;
+            std::mem::take(&mut state.waiters)
+        };
+        // 等待的任务可能已被取消，全部唤醒让它们重新竞争许可
+        waiters.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// 正在执行或排队等待的后台任务数量
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// 正在执行或排队等待的后台任务数量
+///
+/// 包括所有任务池上通过 [`spawn_blocking_task`]、[`spawn_blocking_task_on`] 和 [`spawn_async_task`] 创建的任务
+pub fn background_tasks_in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::Relaxed)
+}
+
+/// 后台任务在执行中的标记，任务结束或被取消时释放
+struct InFlight;
+
+impl InFlight {
+    fn new() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 后台任务 panic 时返回的错误，包含 panic 的负载
+pub struct TaskPanic {
+    message: String,
+    payload: SyncCell<Box<dyn Any + Send>>,
+}
+
+impl TaskPanic {
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        Self {
+            message: panic_message(payload.as_ref()).to_owned(),
+            payload: SyncCell::new(payload),
+        }
+    }
+
+    /// panic 信息，负载不是字符串时为占位文本
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// 取出 panic 的负载，可以传给 [`std::panic::resume_unwind`]
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        SyncCell::to_inner(self.payload)
+    }
+}
+
+impl std::fmt::Debug for TaskPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskPanic")
+            .field("message", &self.message)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Display for TaskPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "background task panicked: {}", self.message)
+    }
+}
+
+impl std::error::Error for TaskPanic {}
+
+/// 在任务池中执行的后台任务，输出任务的返回值
+/// 
+/// 由 [`spawn_blocking_task`]、[`spawn_blocking_task_on`] 和 [`spawn_async_task`] 创建，
+/// 被丢弃时任务会被取消。任务 panic 时输出 `Err(TaskPanic)`，不会影响协程和主线程
+pub struct BackgroundTask<T> {
+    task: Task<std::thread::Result<T>>,
+}
+
+impl<T: Send + 'static> Future for BackgroundTask<T> {
+    type Output = Result<T, TaskPanic>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task)
+            .poll(cx)
+            .map(|result| result.map_err(TaskPanic::new))
+    }
+}
+
+/// 一个通用的函数，用于在 `AsyncComputeTaskPool` 中执行阻塞任务并返回一个Future
+///
+/// 任务完成时会唤醒等待它的协程，参见 [`spawn_blocking_task_on`]
+///
+/// # Example
+/// ```rust,ignore
+/// let result: Result<&str, TaskPanic> = yield spawn_blocking_task(move || {
+///     // 阻塞任务
+///     // ...
+///     return "result";
+/// });
+/// ```
+pub fn spawn_blocking_task<F, T>(task: F) -> BackgroundTask<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_blocking_task_on(BackgroundTaskPool::AsyncCompute, task)
+}
+
+/// 在指定的任务池中执行阻塞任务并返回一个Future
+///
+/// 同一任务池上同时执行的阻塞任务数量受 [`BackgroundTaskPool::max_blocking_tasks`] 限制，
+/// 超出的任务会排队等待，不会额外创建线程
+///
+/// # Example
+/// ```rust,ignore
+/// let bytes = yield spawn_blocking_task_on(BackgroundTaskPool::Io, move || {
+///     std::fs::read("assets/level.ron")
+/// });
+/// let bytes: Vec<u8> = bytes??;
+/// ```
+pub fn spawn_blocking_task_on<F, T>(pool: BackgroundTaskPool, task: F) -> BackgroundTask<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let limit = pool.limit();
+    let in_flight = InFlight::new();
+    let task = pool.task_pool().spawn(async move {
+        let _in_flight = in_flight;
+        let _permit = limit.acquire(pool).await;
+        std::panic::catch_unwind(AssertUnwindSafe(task))
+    });
+
+    BackgroundTask { task }
+}
+
+/// 在 `IoTaskPool` 的执行器上运行一个 Future 并返回一个Future
+///
+/// 适合本身就是异步的 IO 操作，不会占用任务池的线程等待
+///
+/// # Example
+/// ```rust,ignore
+/// let text = yield spawn_async_task(async move {
+///     load_text("config.toml").await
+/// });
+/// let text: String = text?;
+/// ```
+pub fn spawn_async_task<Fut, T>(future: Fut) -> BackgroundTask<T>
+where
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let in_flight = InFlight::new();
+    let task = BackgroundTaskPool::Io.task_pool().spawn(async move {
+        let _in_flight = in_flight;
+        FutureExt::catch_unwind(AssertUnwindSafe(future)).await
+    });
+
+    BackgroundTask { task }
+}
@@ -0,0 +1,152 @@
+//! 测试协程的工具
+//!
+//! [`CoroutineTestApp`] 使用手动时钟，`sleep` 不需要真的等待，协程的时序逻辑可以在几毫秒内测完。
+
+use crate::{CoroutineId, CoroutineInfo, CoroutinePlugin, CoroutineStatus, CoroutineWorldExt, RunningCoroutines};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+/// 使用手动时钟的测试用 [`App`]
+///
+/// 基于 `MinimalPlugins` 和 [`CoroutinePlugin`]，协程的时钟和 Bevy 的 [`Time`] 只在
+/// [`advance_time`](Self::advance_time) 时前进。可以像 [`App`] 一样使用
+///
+/// # Example
+/// ```rust,ignore
+/// #[test]
+/// fn door_opens_after_two_seconds() {
+///     let mut app = CoroutineTestApp::new();
+///     app.start(open_door);
+///     app.assert_yield_count(open_door::id(), 1);
+///
+///     app.advance_time(Duration::from_secs(2));
+///     app.assert_completed(open_door::id());
+/// }
+/// ```
+pub struct CoroutineTestApp {
+    app: App,
+    started_at: Instant,
+}
+
+impl Default for CoroutineTestApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoroutineTestApp {
+    /// 创建测试用的 App
+    pub fn new() -> Self {
+        Self::with_plugin(CoroutinePlugin::default())
+    }
+
+    /// 使用指定的 [`CoroutinePlugin`] 创建测试用的 App
+    pub fn with_plugin(plugin: CoroutinePlugin) -> Self {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, plugin));
+        let clock = &app.world().resource::<RunningCoroutines>().context.clock;
+        clock.set_manual();
+        let started_at = clock.now();
+        app.insert_resource(TimeUpdateStrategy::ManualInstant(started_at));
+        // 一次前进的时间可以很长，不限制虚拟时间每帧的增量
+        app.world_mut().resource_mut::<Time<Virtual>>().set_max_delta(Duration::MAX);
+        Self { app, started_at }
+    }
+
+    /// 启动协程系统，运行到第一个 yield 或结束
+    ///
+    /// # Panics
+    /// 协程系统无法运行时 panic
+    pub fn start<M: 'static>(&mut self, system: impl IntoSystem<(), (), M> + 'static) -> &mut Self {
+        if let Err(e) = self.app.world_mut().start_coroutine(system) {
+            panic!("failed to start coroutine: {e}");
+        }
+        self
+    }
+
+    /// 运行 `frames` 帧，时钟不前进
+    pub fn step_frames(&mut self, frames: u32) -> &mut Self {
+        for _ in 0..frames {
+            self.app.update();
+        }
+        self
+    }
+
+    /// 让时钟前进 `duration` 后运行一帧，到期的 `sleep` 在这一帧恢复
+    pub fn advance_time(&mut self, duration: Duration) -> &mut Self {
+        let clock = &self.app.world().resource::<RunningCoroutines>().context.clock;
+        clock.advance(duration);
+        let now = clock.now();
+        self.app.insert_resource(TimeUpdateStrategy::ManualInstant(now));
+        self.app.update();
+        self
+    }
+
+    /// 创建以来时钟前进的时间
+    pub fn elapsed(&self) -> Duration {
+        self.running().context.clock.now() - self.started_at
+    }
+
+    /// 协程最近一次运行的状态
+    pub fn status(&self, id: CoroutineId) -> Option<&CoroutineStatus> {
+        self.running().status(id)
+    }
+
+    /// 运行中协程的运行时信息
+    pub fn info(&self, id: CoroutineId) -> Option<&CoroutineInfo> {
+        self.running().info(id)
+    }
+
+    /// 断言协程正在运行，且启动以来一共执行了 `count` 次 yield
+    ///
+    /// 比较的是 [`CoroutineInfo::yields`]，立即完成的 Future 和循环中的每一次 yield 都会计数，
+    /// 挂起的位置可以通过 [`CoroutineInfo::waiting_on`] 查看
+    #[track_caller]
+    pub fn assert_yield_count(&self, id: CoroutineId, count: u32) -> &Self {
+        match self.info(id) {
+            Some(info) => assert_eq!(
+                info.yields, count,
+                "coroutine `{id}` has executed {} yields, expected {count} ({info})",
+                info.yields,
+            ),
+            None => panic!(
+                "coroutine `{id}` is not running (status: {:?}), expected it to have executed {count} yields",
+                self.status(id),
+            ),
+        }
+        self
+    }
+
+    /// 断言协程已经成功结束
+    #[track_caller]
+    pub fn assert_completed(&self, id: CoroutineId) -> &Self {
+        match self.status(id) {
+            Some(CoroutineStatus::Completed) => {}
+            status => {
+                let info = self.info(id).map(ToString::to_string).unwrap_or_default();
+                panic!("coroutine `{id}` has not completed (status: {status:?}) {info}");
+            }
+        }
+        self
+    }
+
+    fn running(&self) -> &RunningCoroutines {
+        self.app.world().resource::<RunningCoroutines>()
+    }
+}
+
+impl Deref for CoroutineTestApp {
+    type Target = App;
+
+    fn deref(&self) -> &App {
+        &self.app
+    }
+}
+
+impl DerefMut for CoroutineTestApp {
+    fn deref_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
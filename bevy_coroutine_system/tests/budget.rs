@@ -0,0 +1,92 @@
+//! 每帧推进协程的时间预算
+//!
+//! 协程通过推进手动时钟模拟耗时的工作，预算的计算不依赖真实时间
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::testing::CoroutineTestApp;
+use bevy_coroutine_system::{CoroutineContext, CoroutineStatus};
+use std::time::Duration;
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+/// 协程的运行上下文，用来推进手动时钟
+#[derive(Resource)]
+struct Work(CoroutineContext);
+
+impl Work {
+    /// 模拟 2 毫秒的工作
+    fn run(&self) {
+        self.0.clock().advance(Duration::from_millis(2));
+    }
+}
+
+#[coroutine_system]
+fn heavy_work(work: Res<Work>, mut counter: ResMut<Counter>) {
+    for _ in 0..10 {
+        work.run();
+        counter.0 += 1;
+        yield yield_if_over_budget();
+    }
+}
+
+#[coroutine_system]
+fn ready_futures(work: Res<Work>, mut counter: ResMut<Counter>) {
+    for _ in 0..10 {
+        work.run();
+        counter.0 += 1;
+        yield noop();
+    }
+}
+
+fn app(frame_budget: Option<Duration>) -> CoroutineTestApp {
+    let mut app = CoroutineTestApp::with_plugin(CoroutinePlugin { frame_budget });
+    let context = app.world().resource::<RunningCoroutines>().context.clone();
+    app.insert_resource(Work(context)).init_resource::<Counter>();
+    app
+}
+
+/// 运行到协程结束，返回运行的帧数
+fn run_to_completion(app: &mut App, id: CoroutineId) -> u32 {
+    const MAX_FRAMES: u32 = 100;
+    for frames in 0..MAX_FRAMES {
+        if app.world().resource::<RunningCoroutines>().status(id) != Some(&CoroutineStatus::Running) {
+            return frames;
+        }
+        app.update();
+    }
+    panic!("coroutine `{id}` did not complete within {MAX_FRAMES} frames");
+}
+
+#[test]
+fn without_budget_work_runs_in_one_frame() {
+    let mut app = app(None);
+    app.start(heavy_work);
+    assert_eq!(app.world().resource::<Counter>().0, 10);
+}
+
+#[test]
+fn exhausted_budget_defers_to_the_next_frame() {
+    let mut app = app(Some(Duration::from_millis(5)));
+    app.start(heavy_work);
+    // 第三次工作后花费了 6 毫秒，超出 5 毫秒的预算
+    assert_eq!(app.world().resource::<Counter>().0, 3);
+
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 6);
+    assert_eq!(run_to_completion(&mut app, heavy_work::id()), 2);
+    assert_eq!(app.world().resource::<Counter>().0, 10);
+}
+
+#[test]
+fn ready_futures_stop_when_budget_is_exhausted() {
+    let mut app = app(Some(Duration::from_millis(5)));
+    app.start(ready_futures);
+    assert_eq!(app.world().resource::<Counter>().0, 3);
+
+    assert_eq!(run_to_completion(&mut app, ready_futures::id()), 3);
+    assert_eq!(app.world().resource::<Counter>().0, 10);
+}
@@ -0,0 +1,96 @@
+//! 协程与系统之间的通道
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::testing::CoroutineTestApp;
+use bevy_coroutine_system::TryRecvError;
+
+#[derive(Resource)]
+struct Jobs {
+    sender: Option<CoroutineSender<u32>>,
+    receiver: CoroutineReceiver<u32>,
+}
+
+#[derive(Resource, Default)]
+struct Done(Vec<u32>);
+
+#[coroutine_system]
+fn consumer(jobs: Res<Jobs>, mut done: ResMut<Done>) {
+    let receiver = jobs.receiver.clone();
+    while let Some(job) = yield receiver.recv() {
+        done.0.push(job);
+    }
+}
+
+#[derive(Resource)]
+struct Results(CoroutineSender<&'static str>);
+
+#[coroutine_system]
+fn producer(results: Res<Results>) {
+    let sender = results.0.clone();
+    sender.send("loading").unwrap();
+    yield next_frame();
+    sender.send("loaded").unwrap();
+}
+
+fn app() -> (CoroutineTestApp, CoroutineSender<u32>) {
+    let (sender, receiver) = coroutine_channel();
+    let mut app = CoroutineTestApp::new();
+    app.insert_resource(Jobs {
+        sender: Some(sender.clone()),
+        receiver,
+    })
+    .init_resource::<Done>();
+    (app, sender)
+}
+
+#[test]
+fn coroutine_waits_for_sent_values() {
+    let (mut app, sender) = app();
+    app.start(consumer).step_frames(2);
+    assert!(app.world().resource::<Done>().0.is_empty());
+
+    sender.send(1).unwrap();
+    std::thread::spawn(move || sender.send(2).unwrap()).join().unwrap();
+    app.step_frames(1);
+    assert_eq!(app.world().resource::<Done>().0, [1, 2]);
+    app.assert_yield_count(consumer::id(), 3);
+}
+
+#[test]
+fn dropping_all_senders_ends_the_receiving_coroutine() {
+    let (mut app, sender) = app();
+    app.start(consumer);
+    sender.send(7).unwrap();
+    drop(sender);
+    app.world_mut().resource_mut::<Jobs>().sender = None;
+
+    app.step_frames(1).assert_completed(consumer::id());
+    assert_eq!(app.world().resource::<Done>().0, [7]);
+}
+
+#[test]
+fn systems_receive_values_sent_by_coroutines() {
+    let (sender, receiver) = coroutine_channel();
+    let mut app = CoroutineTestApp::new();
+    app.insert_resource(Results(sender));
+    app.start(producer);
+    assert_eq!(receiver.try_recv(), Ok("loading"));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+    app.step_frames(1).assert_completed(producer::id());
+    assert_eq!(receiver.try_recv(), Ok("loaded"));
+
+    app.world_mut().remove_resource::<Results>();
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+}
+
+#[test]
+fn sending_to_a_closed_channel_returns_the_value() {
+    let (sender, receiver) = coroutine_channel();
+    drop(receiver);
+    assert!(sender.is_closed());
+    assert_eq!(sender.send(3).unwrap_err().0, 3);
+}
@@ -0,0 +1,145 @@
+//! 通过 `Commands`、`World` 和 `EntityCommands` 启动和取消协程
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::{CoroutineStatus, OwnedCoroutines};
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+#[coroutine_system]
+fn count_frames(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+    counter.0 += 10;
+    yield next_frame();
+    counter.0 += 100;
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(CoroutinePlugin::default()).init_resource::<Counter>();
+    app
+}
+
+fn status(app: &App) -> Option<CoroutineStatus> {
+    app.world()
+        .resource::<RunningCoroutines>()
+        .status(count_frames::id())
+        .cloned()
+}
+
+#[test]
+fn commands_start_and_cancel_coroutines() {
+    let mut app = app();
+    app.world_mut()
+        .run_system_cached(|mut commands: Commands| commands.start_coroutine(count_frames))
+        .unwrap();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 11);
+
+    app.world_mut()
+        .run_system_cached(|mut commands: Commands| commands.cancel_coroutine(count_frames::id()))
+        .unwrap();
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 11);
+    assert_eq!(status(&app), Some(CoroutineStatus::Cancelled));
+}
+
+#[test]
+fn world_starts_and_cancels_coroutines() {
+    let mut app = app();
+    app.world_mut().start_coroutine(count_frames).unwrap();
+    assert_eq!(status(&app), Some(CoroutineStatus::Running));
+
+    app.world_mut().cancel_coroutine(count_frames::id());
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+    assert_eq!(status(&app), Some(CoroutineStatus::Cancelled));
+}
+
+#[test]
+fn despawning_the_owner_cancels_the_coroutine() {
+    let mut app = app();
+    let entity = app.world_mut().spawn_empty().id();
+    app.world_mut().commands().entity(entity).start_coroutine(count_frames);
+    app.world_mut().flush();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+    assert_eq!(
+        app.world().get::<OwnedCoroutines>(entity).unwrap().0,
+        [count_frames::id()]
+    );
+
+    app.world_mut().despawn(entity);
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+    assert_eq!(status(&app), Some(CoroutineStatus::Cancelled));
+}
+
+#[coroutine_system]
+fn tick(mut counter: ResMut<Counter>) {
+    loop {
+        counter.0 += 1;
+        yield next_frame();
+    }
+}
+
+#[test]
+fn coroutine_is_cancelled_when_its_last_owner_is_despawned() {
+    let mut app = app();
+    let first = app.world_mut().spawn_empty().id();
+    let second = app.world_mut().spawn_empty().id();
+    app.world_mut().commands().entity(first).start_coroutine(tick);
+    app.world_mut().commands().entity(second).start_coroutine(tick);
+    app.world_mut().flush();
+    // 第二次启动恢复了同一个实例，两个实体共同拥有它
+    assert_eq!(app.world().resource::<Counter>().0, 2);
+    let owners = |app: &App| app.world().resource::<RunningCoroutines>().owners(tick::id()).to_vec();
+    assert_eq!(owners(&app), [first, second]);
+
+    app.world_mut().despawn(first);
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 3);
+    assert_eq!(owners(&app), [second]);
+
+    app.world_mut().despawn(second);
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 3);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(tick::id()),
+        Some(&CoroutineStatus::Cancelled)
+    );
+    assert!(owners(&app).is_empty());
+}
+
+#[derive(Component)]
+struct Health(u32);
+
+#[coroutine_system]
+fn regenerate(mut owners: Query<(&OwnedCoroutines, &mut Health)>) {
+    loop {
+        for (owned, mut health) in owners.iter_mut() {
+            if owned.contains(regenerate::id()) {
+                health.0 += 1;
+            }
+        }
+        yield next_frame();
+    }
+}
+
+#[test]
+fn coroutines_find_their_owners() {
+    let mut app = app();
+    let owner = app.world_mut().spawn(Health(0)).id();
+    let other = app.world_mut().spawn(Health(0)).id();
+    app.world_mut().commands().entity(owner).start_coroutine(regenerate);
+    app.world_mut().flush();
+    app.update();
+
+    assert_eq!(app.world().get::<Health>(owner).unwrap().0, 2);
+    assert_eq!(app.world().get::<Health>(other).unwrap().0, 0);
+}
@@ -0,0 +1,9 @@
+//! 宏在编译期给出的错误
+//!
+//! 修改错误信息后使用 `TRYBUILD=overwrite cargo test --test compile_fail` 更新 `.stderr` 文件
+
+#[test]
+fn compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,7 @@
+//! 宏不应报错的写法，如在 yield 之前复制出不含借用的数据
+
+#[test]
+fn compile_pass() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+}
@@ -0,0 +1,62 @@
+//! 协程的诊断信息
+
+#![feature(coroutines)]
+
+use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore};
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::background_tasks_in_flight;
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+#[coroutine_system]
+fn two_frames() {
+    yield next_frame();
+    yield next_frame();
+}
+
+/// 通知后台任务结束
+static RELEASE: Mutex<Option<mpsc::Sender<()>>> = Mutex::new(None);
+
+#[coroutine_system]
+fn waits_for_background_task() {
+    let (sender, receiver) = mpsc::channel();
+    *RELEASE.lock().unwrap() = Some(sender);
+    let _ = yield spawn_blocking_task(move || receiver.recv());
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((CoroutinePlugin::default(), CoroutineDiagnosticsPlugin));
+    app
+}
+
+fn latest(app: &App, path: &DiagnosticPath) -> Option<f64> {
+    app.world().resource::<DiagnosticsStore>().get(path)?.value()
+}
+
+#[test]
+fn coroutine_diagnostics_are_measured() {
+    let mut app = app();
+    app.world_mut().start_coroutine(two_frames).unwrap();
+    app.update();
+
+    assert_eq!(latest(&app, &CoroutineDiagnosticsPlugin::ACTIVE_COROUTINES), Some(1.0));
+    assert_eq!(latest(&app, &CoroutineDiagnosticsPlugin::RESUMES), Some(1.0));
+    assert!(latest(&app, &CoroutineDiagnosticsPlugin::RESUME_TIME_AVG).is_some());
+    assert!(latest(&app, &CoroutineDiagnosticsPlugin::RESUME_TIME_MAX).is_some());
+
+    app.update();
+    assert_eq!(latest(&app, &CoroutineDiagnosticsPlugin::ACTIVE_COROUTINES), Some(0.0));
+}
+
+#[test]
+fn background_tasks_in_flight_are_counted() {
+    let mut app = app();
+    app.world_mut().start_coroutine(waits_for_background_task).unwrap();
+    app.update();
+    assert!(background_tasks_in_flight() >= 1);
+    assert!(latest(&app, &CoroutineDiagnosticsPlugin::BACKGROUND_TASKS).unwrap() >= 1.0);
+
+    RELEASE.lock().unwrap().take().unwrap().send(()).unwrap();
+}
@@ -0,0 +1,253 @@
+//! 驱动系统恢复被唤醒的协程
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::testing::CoroutineTestApp;
+use bevy_coroutine_system::{CoroutineReceiver, CoroutineStatus};
+use std::task::Wake;
+use std::time::Duration;
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+/// 协程恢复的顺序
+#[derive(Resource, Default)]
+struct Log(Vec<&'static str>);
+
+#[coroutine_system]
+fn first(mut log: ResMut<Log>) {
+    yield next_frame();
+    log.0.push("first");
+}
+
+#[coroutine_system]
+fn second(mut log: ResMut<Log>) {
+    yield next_frame();
+    log.0.push("second");
+}
+
+#[coroutine_system(priority = 10)]
+fn urgent(mut log: ResMut<Log>) {
+    yield next_frame();
+    log.0.push("urgent");
+}
+
+#[coroutine_system(priority = -1)]
+fn background(mut log: ResMut<Log>) {
+    yield next_frame();
+    log.0.push("background");
+}
+
+#[coroutine_system]
+fn count_frames(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+    counter.0 += 10;
+    yield next_frame();
+    counter.0 += 100;
+}
+
+/// 永不返回的协程体
+#[coroutine_system]
+fn count_forever(mut counter: ResMut<Counter>) {
+    loop {
+        counter.0 += 1;
+        yield next_frame();
+    }
+}
+
+#[derive(Resource)]
+struct Inbox(CoroutineReceiver<u32>);
+
+#[coroutine_system]
+fn wait_for_value(inbox: Res<Inbox>, mut counter: ResMut<Counter>) {
+    let receiver = inbox.0.clone();
+    while let Some(value) = yield receiver.recv() {
+        counter.0 += value;
+    }
+}
+
+#[coroutine_system]
+fn sleep_long() {
+    yield sleep(Duration::from_secs(3600));
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(CoroutinePlugin::default()).init_resource::<Counter>();
+    app
+}
+
+#[test]
+fn coroutine_started_without_registration_is_driven() {
+    let mut app = app();
+    app.world_mut().run_system_cached(count_frames).unwrap();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(app.world().resource::<Counter>().0, 111);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(count_frames::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn added_coroutine_is_driven() {
+    let mut app = app();
+    app.add_coroutine(count_frames);
+    app.world_mut().run_system_cached(count_frames).unwrap();
+
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(app.world().resource::<Counter>().0, 111);
+}
+
+#[test]
+fn coroutines_resume_by_priority_then_registration_order() {
+    for _ in 0..8 {
+        let mut app = app();
+        app.init_resource::<Log>();
+        let world = app.world_mut();
+        world.run_system_cached(background).unwrap();
+        world.run_system_cached(second).unwrap();
+        world.run_system_cached(first).unwrap();
+        world.run_system_cached(urgent).unwrap();
+
+        app.update();
+        assert_eq!(app.world().resource::<Log>().0, ["urgent", "second", "first", "background"]);
+    }
+}
+
+#[test]
+fn never_returning_coroutine_keeps_running() {
+    let mut app = app();
+    app.world_mut().run_system_cached(count_forever).unwrap();
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(app.world().resource::<Counter>().0, 4);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(count_forever::id()),
+        Some(&CoroutineStatus::Running)
+    );
+}
+
+fn resumes(app: &App, id: CoroutineId) -> u32 {
+    app.world().resource::<RunningCoroutines>().info(id).unwrap().resumes
+}
+
+#[test]
+fn pending_coroutine_is_not_resumed_until_woken() {
+    let mut app = app();
+    let (sender, receiver) = coroutine_channel();
+    app.insert_resource(Inbox(receiver));
+    app.world_mut().run_system_cached(wait_for_value).unwrap();
+    let started = resumes(&app, wait_for_value::id());
+
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(resumes(&app, wait_for_value::id()), started);
+
+    sender.send(7).unwrap();
+    app.update();
+    assert_eq!(resumes(&app, wait_for_value::id()), started + 1);
+    assert_eq!(app.world().resource::<Counter>().0, 7);
+
+    app.update();
+    assert_eq!(resumes(&app, wait_for_value::id()), started + 1);
+}
+
+fn timers(app: &App) -> usize {
+    app.world().resource::<RunningCoroutines>().context.timers().len()
+}
+
+#[test]
+fn repolled_sleep_registers_one_timer() {
+    let mut app = app();
+    app.world_mut().run_system_cached(sleep_long).unwrap();
+    assert_eq!(timers(&app), 1);
+
+    // 唤醒协程让 sleep 在到期前被再次轮询
+    for _ in 0..3 {
+        let running = app.world().resource::<RunningCoroutines>();
+        Wake::wake_by_ref(&running.systems[&sleep_long::id()]);
+        app.update();
+    }
+    assert_eq!(timers(&app), 1);
+}
+
+#[test]
+fn cancelled_coroutine_drops_its_timers() {
+    let mut app = app();
+    app.world_mut().run_system_cached(sleep_long).unwrap();
+    assert_eq!(timers(&app), 1);
+
+    app.world_mut().cancel_coroutine(sleep_long::id());
+    app.update();
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(sleep_long::id()),
+        Some(&CoroutineStatus::Cancelled)
+    );
+    assert_eq!(timers(&app), 0);
+}
+
+#[derive(Component)]
+struct Player;
+
+#[coroutine_system]
+fn follow_player(player: Single<Entity, With<Player>>, mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield sleep(Duration::from_secs(1));
+    counter.0 += 10;
+}
+
+fn test_app() -> CoroutineTestApp {
+    let mut app = CoroutineTestApp::new();
+    app.init_resource::<Counter>();
+    app
+}
+
+#[test]
+fn invalid_params_retry_the_wake_next_frame() {
+    let mut app = test_app();
+    let player = app.world_mut().spawn(Player).id();
+    app.start(follow_player);
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+
+    // 定时器到期时 `Single` 没有匹配的实体，协程系统无法运行
+    app.world_mut().despawn(player);
+    app.advance_time(Duration::from_secs(1)).step_frames(1);
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+    assert_eq!(app.status(follow_player::id()), Some(&CoroutineStatus::Running));
+
+    // 唤醒没有丢失，参数重新有效后协程被恢复
+    app.world_mut().spawn(Player);
+    app.step_frames(1).assert_completed(follow_player::id());
+    assert_eq!(app.world().resource::<Counter>().0, 11);
+}
+
+#[test]
+fn cancel_applies_while_params_are_invalid() {
+    let mut app = test_app();
+    let player = app.world_mut().spawn(Player).id();
+    app.start(follow_player);
+
+    app.world_mut().despawn(player);
+    app.world_mut().cancel_coroutine(follow_player::id());
+    app.step_frames(1);
+    assert_eq!(app.status(follow_player::id()), Some(&CoroutineStatus::Cancelled));
+    assert_eq!(app.world().resource::<RunningCoroutines>().context.timers().len(), 0);
+
+    // 重新启动时从头开始，而不是恢复被取消的协程
+    app.world_mut().spawn(Player);
+    app.start(follow_player);
+    assert_eq!(app.world().resource::<Counter>().0, 2);
+    assert_eq!(app.status(follow_player::id()), Some(&CoroutineStatus::Running));
+}
@@ -0,0 +1,117 @@
+//! 独占 `World` 的协程
+
+#![feature(coroutines)]
+
+use bevy::ecs::component::Tick;
+use bevy::ecs::error::ErrorContext;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::CoroutineStatus;
+use std::sync::Mutex;
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+#[derive(Component)]
+struct Marker;
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct Custom;
+
+fn count_in_custom(mut counter: ResMut<Counter>) {
+    counter.0 += 100;
+}
+
+#[coroutine_system]
+fn build_scene(world: &mut World) {
+    world.spawn(Marker);
+    world.resource_mut::<Counter>().0 += 1;
+
+    yield next_frame();
+
+    // 恢复后的 world 是这一次运行传入的
+    let markers = world.query::<&Marker>().iter(world).count() as u32;
+    world.resource_mut::<Counter>().0 += markers * 10;
+
+    // 协程体可以运行调度
+    world.run_schedule(Custom);
+}
+
+#[coroutine_system]
+fn failing_exclusive(world: &mut World) -> Result<(), BevyError> {
+    yield next_frame();
+    world.get_resource::<Counter>().ok_or("missing counter")?;
+    Err("exclusive failure".into())
+}
+
+/// 协程系统第一次运行时的 tick
+#[derive(Resource)]
+struct FirstRun(Tick);
+
+#[coroutine_system]
+fn failing_after_first_run(world: &mut World) -> Result<(), BevyError> {
+    let tick = world.change_tick();
+    world.insert_resource(FirstRun(tick));
+    yield next_frame();
+    Err("exclusive failure".into())
+}
+
+/// 错误处理函数收到的 `last_run`
+static LAST_RUN: Mutex<Option<Tick>> = Mutex::new(None);
+
+fn record_last_run(_error: BevyError, context: ErrorContext) {
+    if let ErrorContext::System { last_run, .. } = context {
+        *LAST_RUN.lock().unwrap() = Some(last_run);
+    }
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(CoroutinePlugin::default())
+        .init_resource::<Counter>()
+        .add_systems(Custom, count_in_custom);
+    app
+}
+
+#[test]
+fn exclusive_coroutine_is_driven() {
+    let mut app = app();
+    app.world_mut().run_system_cached(build_scene).unwrap();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 111);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(build_scene::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn exclusive_coroutine_errors_are_recorded() {
+    let mut app = app();
+    app.world_mut()
+        .resource_mut::<RunningCoroutines>()
+        .error_handler = bevy::ecs::error::ignore;
+    app.world_mut().run_system_cached(failing_exclusive).unwrap();
+    app.update();
+
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(failing_exclusive::id()),
+        Some(&CoroutineStatus::Failed("exclusive failure".into()))
+    );
+}
+
+#[test]
+fn exclusive_coroutine_errors_see_the_system_last_run() {
+    let mut app = app();
+    app.world_mut().resource_mut::<RunningCoroutines>().error_handler = record_last_run;
+    app.world_mut().run_system_cached(failing_after_first_run).unwrap();
+    app.update();
+
+    // 系统上一次运行就是第一次运行，而不是 World 中最近运行的其他系统
+    let first_run = app.world().resource::<FirstRun>().0;
+    assert_eq!(*LAST_RUN.lock().unwrap(), Some(first_run));
+    assert_ne!(app.world().last_change_tick(), first_run);
+}
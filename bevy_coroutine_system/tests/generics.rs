@@ -0,0 +1,89 @@
+//! 带有类型参数的协程系统
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::CoroutineStatus;
+use std::fmt::Debug;
+
+#[derive(Component)]
+struct Enemy;
+
+#[derive(Component)]
+struct Bullet;
+
+#[derive(Resource, Default)]
+struct Log(Vec<String>);
+
+#[coroutine_system]
+fn despawn_after<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    yield next_frame();
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[coroutine_system]
+fn log_resource<R>(resource: Res<R>, mut log: ResMut<Log>)
+where
+    R: Resource + Debug,
+{
+    log.0.push(format!("{:?}", **resource));
+    yield next_frame();
+    log.0.push(format!("{:?}", **resource));
+}
+
+#[derive(Resource, Debug)]
+struct Score(u32);
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(CoroutinePlugin::default()).init_resource::<Log>();
+    app
+}
+
+fn status(app: &App, id: CoroutineId) -> Option<CoroutineStatus> {
+    app.world().resource::<RunningCoroutines>().status(id).cloned()
+}
+
+#[test]
+fn generic_instances_have_their_own_ids() {
+    assert_eq!(despawn_after::id::<Enemy>(), despawn_after::id::<Enemy>());
+    assert_ne!(despawn_after::id::<Enemy>(), despawn_after::id::<Bullet>());
+    assert!(despawn_after::id::<Enemy>().to_string().ends_with("::despawn_after<generics::Enemy>"));
+}
+
+#[test]
+fn generic_instances_start_and_resume_independently() {
+    let mut app = app();
+    let enemy = app.world_mut().spawn(Enemy).id();
+    let bullet = app.world_mut().spawn(Bullet).id();
+
+    app.world_mut().start_coroutine(despawn_after::<Enemy>).unwrap();
+    assert_eq!(status(&app, despawn_after::id::<Enemy>()), Some(CoroutineStatus::Running));
+    assert_eq!(status(&app, despawn_after::id::<Bullet>()), None);
+
+    app.update();
+    assert_eq!(status(&app, despawn_after::id::<Enemy>()), Some(CoroutineStatus::Completed));
+    assert!(app.world().get_entity(enemy).is_err());
+    assert!(app.world().get_entity(bullet).is_ok());
+
+    app.world_mut().start_coroutine(despawn_after::<Bullet>).unwrap();
+    app.update();
+    assert_eq!(status(&app, despawn_after::id::<Bullet>()), Some(CoroutineStatus::Completed));
+    assert!(app.world().get_entity(bullet).is_err());
+}
+
+#[test]
+fn generic_params_are_refetched_after_yield() {
+    let mut app = app();
+    app.insert_resource(Score(1));
+    app.add_coroutine(log_resource::<Score>);
+    app.world_mut().start_coroutine(log_resource::<Score>).unwrap();
+
+    app.world_mut().resource_mut::<Score>().0 = 2;
+    app.update();
+    assert_eq!(app.world().resource::<Log>().0, ["Score(1)", "Score(2)"]);
+    assert_eq!(status(&app, log_resource::id::<Score>()), Some(CoroutineStatus::Completed));
+}
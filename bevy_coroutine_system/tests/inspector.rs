@@ -0,0 +1,72 @@
+//! 运行中协程的运行时信息
+
+#![feature(coroutines)]
+
+use bevy::diagnostic::{FrameCount, FrameCountPlugin};
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use std::time::Duration;
+
+#[derive(Resource, Default)]
+struct YieldLines(Vec<u32>);
+
+#[coroutine_system]
+fn cutscene(mut lines: ResMut<YieldLines>) {
+    lines.0.push(line!() + 1);
+    yield next_frame();
+    lines.0.push(line!() + 1);
+    yield sleep(Duration::from_secs(60));
+}
+
+fn start_on_second_frame(frame_count: Res<FrameCount>, mut commands: Commands) {
+    if frame_count.0 == 2 {
+        commands.start_coroutine(cutscene);
+    }
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((FrameCountPlugin, CoroutinePlugin::default()))
+        .init_resource::<YieldLines>()
+        .add_systems(Update, start_on_second_frame);
+    app
+}
+
+#[test]
+fn running_coroutines_can_be_inspected() {
+    let mut app = app();
+    for _ in 0..3 {
+        app.update();
+    }
+    let running = app.world().resource::<RunningCoroutines>();
+    let info = running.info(cutscene::id()).unwrap();
+    assert_eq!(info.started_frame, 2);
+    assert_eq!(info.resumes, 1);
+    let site = info.waiting_on.unwrap();
+    assert!(site.future.contains("NextFrame"), "{}", site.future);
+    assert!(site.location.file().ends_with("inspector.rs"), "{}", site.location);
+    assert_eq!(site.location.line(), app.world().resource::<YieldLines>().0[0]);
+
+    app.update();
+    let running = app.world().resource::<RunningCoroutines>();
+    let info = running.info(cutscene::id()).unwrap();
+    assert_eq!(info.resumes, 2);
+    let site = info.waiting_on.unwrap();
+    assert!(site.future.contains("Sleep"), "{}", site.future);
+    assert_eq!(site.location.line(), app.world().resource::<YieldLines>().0[1]);
+    assert!(info.to_string().contains("waiting on"));
+    assert_eq!(running.inspect().map(|(id, _)| id).collect::<Vec<_>>(), [cutscene::id()]);
+}
+
+#[test]
+fn finished_coroutines_are_not_inspected() {
+    let mut app = app();
+    for _ in 0..3 {
+        app.update();
+    }
+    app.world_mut().cancel_coroutine(cutscene::id());
+    app.update();
+    let running = app.world().resource::<RunningCoroutines>();
+    assert!(running.info(cutscene::id()).is_none());
+    assert_eq!(running.inspect().count(), 0);
+}
@@ -0,0 +1,219 @@
+//! `#[coroutine_system(...)]` 的参数
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::CoroutineStatus;
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+#[coroutine_system(name = "ignored", retrigger = Ignore, tags = ["test"])]
+fn ignore_retrigger(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+    counter.0 += 10;
+}
+
+#[coroutine_system(retrigger = Restart)]
+fn restart_on_retrigger(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+    counter.0 += 10;
+}
+
+#[coroutine_system(name = "ignored")]
+fn same_name(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+}
+
+#[coroutine_system(schedule = PostUpdate)]
+fn late_counter(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+    counter.0 += 10;
+    yield next_frame();
+    counter.0 += 100;
+}
+
+#[coroutine_system(repeat)]
+fn repeating(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+}
+
+#[derive(States, Default, Clone, PartialEq, Eq, Hash, Debug)]
+enum GameState {
+    #[default]
+    Playing,
+    Paused,
+}
+
+#[coroutine_system(state = GameState::Playing)]
+fn while_playing(mut counter: ResMut<Counter>) {
+    loop {
+        counter.0 += 1;
+        yield next_frame();
+    }
+}
+
+#[coroutine_system]
+fn generic_counter<const N: u32>(mut counter: ResMut<Counter>) {
+    counter.0 += N;
+    yield next_frame();
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.init_resource::<Counter>();
+    world.init_resource::<RunningCoroutines>();
+    world
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((StatesPlugin, CoroutinePlugin::default()))
+        .init_resource::<Counter>()
+        .init_state::<GameState>();
+    app
+}
+
+#[test]
+fn name_and_tags_are_recorded() {
+    let mut world = world();
+    assert_eq!(ignore_retrigger::id().name(), "ignored");
+
+    world.run_system_cached(ignore_retrigger).unwrap();
+    let running = world.resource::<RunningCoroutines>();
+    assert_eq!(running.tagged("test").collect::<Vec<_>>(), [ignore_retrigger::id()]);
+    assert_eq!(running.config(ignore_retrigger::id()).unwrap().retrigger, CoroutineRetrigger::Ignore);
+}
+
+#[test]
+fn ignored_retrigger_does_not_resume() {
+    let mut world = world();
+    world.run_system_cached(ignore_retrigger).unwrap();
+    world.run_system_cached(ignore_retrigger).unwrap();
+    world.run_system_cached(ignore_retrigger).unwrap();
+    assert_eq!(world.resource::<Counter>().0, 1);
+}
+
+#[test]
+fn restart_retrigger_starts_over() {
+    let mut world = world();
+    world.run_system_cached(restart_on_retrigger).unwrap();
+    world.run_system_cached(restart_on_retrigger).unwrap();
+    assert_eq!(world.resource::<Counter>().0, 2);
+}
+
+#[test]
+fn cancelled_coroutine_is_not_resumed() {
+    let mut world = world();
+    world.run_system_cached(restart_on_retrigger).unwrap();
+    world.resource_mut::<RunningCoroutines>().cancel(restart_on_retrigger::id());
+    world.run_system_cached(restart_on_retrigger).unwrap();
+
+    let running = world.resource::<RunningCoroutines>();
+    assert_eq!(running.status(restart_on_retrigger::id()), Some(&CoroutineStatus::Cancelled));
+    assert_eq!(world.resource::<Counter>().0, 1);
+}
+
+#[test]
+fn ids_are_derived_from_the_system_type() {
+    // 名称相同的函数和同一泛型函数的不同实例都有各自的标识符
+    assert_ne!(same_name::id(), ignore_retrigger::id());
+    assert_ne!(generic_counter::id::<1>(), generic_counter::id::<2>());
+    assert_eq!(generic_counter::id::<1>(), generic_counter::id::<1>());
+    assert!(generic_counter::id::<2>().to_string().ends_with("::generic_counter<2>"));
+
+    let mut world = world();
+    world.run_system_cached(generic_counter::<1>).unwrap();
+    world.run_system_cached(generic_counter::<2>).unwrap();
+    let running = world.resource::<RunningCoroutines>();
+    assert_eq!(running.status(generic_counter::id::<1>()), Some(&CoroutineStatus::Running));
+    assert_eq!(running.status(generic_counter::id::<2>()), Some(&CoroutineStatus::Running));
+    assert_eq!(running.status(same_name::id()), None);
+}
+
+/// `Update` 中记录的计数，`PostUpdate` 中的协程在它之后恢复
+#[derive(Resource, Default)]
+struct SeenInUpdate(Vec<u32>);
+
+fn record_counter(counter: Res<Counter>, mut seen: ResMut<SeenInUpdate>) {
+    seen.0.push(counter.0);
+}
+
+#[test]
+fn coroutine_is_driven_in_its_schedule() {
+    let mut app = app();
+    app.init_resource::<SeenInUpdate>().add_systems(Update, record_counter);
+    app.world_mut().start_coroutine(late_counter).unwrap();
+
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<SeenInUpdate>().0, [1, 11]);
+    assert_eq!(app.world().resource::<Counter>().0, 111);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(late_counter::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn coroutine_started_in_its_schedule_is_driven() {
+    let mut app = app();
+    app.add_systems(PostUpdate, |mut commands: Commands, mut started: Local<bool>| {
+        if !std::mem::replace(&mut *started, true) {
+            commands.start_coroutine(late_counter);
+        }
+    });
+
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 111);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(late_counter::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn repeating_coroutine_restarts_after_completing() {
+    let mut app = app();
+    app.world_mut().start_coroutine(repeating).unwrap();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+
+    let mut counts = Vec::new();
+    for _ in 0..6 {
+        app.update();
+        counts.push(app.world().resource::<Counter>().0);
+    }
+    // 结束的那一帧之后重新开始，每两帧计数一次
+    assert_eq!(counts, [1, 2, 2, 3, 3, 4]);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(repeating::id()),
+        Some(&CoroutineStatus::Running)
+    );
+}
+
+#[test]
+fn leaving_the_state_cancels_the_coroutine() {
+    let mut app = app();
+    app.world_mut().start_coroutine(while_playing).unwrap();
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 2);
+
+    app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Paused);
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Counter>().0, 2);
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(while_playing::id()),
+        Some(&CoroutineStatus::Cancelled)
+    );
+}
@@ -0,0 +1,114 @@
+//! 协程和后台任务中的 panic
+
+#![feature(coroutines)]
+
+use bevy::log::tracing::field::{Field, Visit};
+use bevy::log::tracing::{self, Event, Level, Subscriber};
+use bevy::log::tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use bevy::log::tracing_subscriber::Registry;
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::CoroutineStatus;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+#[derive(Resource, Default)]
+struct Outcome(Option<Result<u32, String>>);
+
+#[coroutine_system]
+fn panicking(mut counter: ResMut<Counter>) {
+    counter.0 += 1;
+    yield next_frame();
+    panic!("coroutine failure");
+}
+
+#[coroutine_system]
+fn blocking_panic(mut outcome: ResMut<Outcome>) {
+    let result = yield spawn_blocking_task(|| -> u32 { panic!("blocking failure") });
+    outcome.0 = Some(result.map_err(|e| e.message().to_owned()));
+}
+
+/// 记录错误日志
+#[derive(Clone, Default)]
+struct ErrorLogs(Arc<Mutex<Vec<String>>>);
+
+impl<S: Subscriber> Layer<S> for ErrorLogs {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() == Level::ERROR {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0.lock().unwrap().push(message.0);
+        }
+    }
+}
+
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(CoroutinePlugin::default())
+        .init_resource::<Counter>()
+        .init_resource::<Outcome>();
+    app
+}
+
+#[test]
+fn panicking_coroutine_is_logged_and_removed() {
+    let mut app = app();
+    app.world_mut().start_coroutine(panicking).unwrap();
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+    assert!(app.world().resource::<RunningCoroutines>().info(panicking::id()).is_some());
+
+    let logs = ErrorLogs::default();
+    tracing::subscriber::with_default(Registry::default().with(logs.clone()), || app.update());
+
+    let running = app.world().resource::<RunningCoroutines>();
+    assert_eq!(
+        running.status(panicking::id()),
+        Some(&CoroutineStatus::Failed("panicked: coroutine failure".into()))
+    );
+    assert!(!running.systems.contains_key(&panicking::id()));
+    assert!(running.info(panicking::id()).is_none());
+    assert_eq!(
+        *logs.0.lock().unwrap(),
+        [format!("Coroutine `{}` panicked: coroutine failure", panicking::id())]
+    );
+
+    // 失败的协程可以重新启动
+    app.world_mut().start_coroutine(panicking).unwrap();
+    assert_eq!(app.world().resource::<Counter>().0, 2);
+}
+
+#[test]
+fn panicking_blocking_task_yields_task_panic() {
+    let mut app = app();
+    app.world_mut().start_coroutine(blocking_panic).unwrap();
+
+    // 捕获 panic 的回溯可能很慢，最多等待 10 秒
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while app.world().resource::<Outcome>().0.is_none() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(1));
+        app.update();
+    }
+
+    assert_eq!(
+        app.world().resource::<Outcome>().0,
+        Some(Err("blocking failure".to_owned()))
+    );
+    assert_eq!(
+        app.world().resource::<RunningCoroutines>().status(blocking_panic::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
@@ -0,0 +1,338 @@
+//! 系统参数在协程中的传递
+//!
+//! 这些测试也需要在 Miri 下通过：
+//! `MIRIFLAGS=-Zmiri-disable-isolation cargo miri test -p bevy_coroutine_system --test params`
+
+#![feature(coroutines)]
+
+use bevy::ecs::system::{SystemParam, SystemState};
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::{into_coroutine_future, CoroutineStatus};
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+#[derive(Component)]
+struct Marker;
+
+#[coroutine_system]
+fn count_markers(mut counter: ResMut<Counter>, markers: Query<&Marker>, mut commands: Commands) {
+    counter.0 += markers.iter().count() as u32;
+    commands.spawn(Marker);
+
+    yield next_frame();
+
+    // 恢复后使用的是这一次运行获取的参数，能看到上一帧生成的实体
+    counter.0 += markers.iter().count() as u32 * 10;
+
+    // 立即完成的 Future 会在同一次运行中再次恢复协程
+    yield noop();
+    counter.0 += 100;
+}
+
+#[derive(SystemParam)]
+struct Markers<'w, 's> {
+    query: Query<'w, 's, &'static Marker>,
+}
+
+#[coroutine_system]
+fn count_markers_destructured(
+    (mut counter, Markers { query }): (ResMut<Counter>, Markers),
+    _: Commands,
+    mut commands: Commands,
+) {
+    counter.0 += query.iter().count() as u32;
+    commands.spawn(Marker);
+
+    yield next_frame();
+
+    // 解构出的绑定在恢复后同样指向这一次运行获取的参数
+    counter.0 += query.iter().count() as u32 * 10;
+}
+
+#[coroutine_system]
+fn yield_in_expressions(mut counter: ResMut<Counter>, markers: Query<&Marker>, mut commands: Commands) {
+    commands.spawn(Marker);
+
+    // 表达式中的 yield 恢复后同样会重新获取参数
+    let count = 1 + match yield next_frame() {
+        () if markers.is_empty() => 0,
+        () => markers.iter().count() as u32,
+    };
+    if yield std::future::ready(true) {
+        counter.0 += count;
+    }
+    counter.0 += u32::from(yield std::future::ready(10u8)) * markers.iter().count() as u32;
+}
+
+#[coroutine_system]
+fn control_flow(mut counter: ResMut<Counter>, markers: Query<&Marker>, mut commands: Commands) {
+    let mut pending = vec![1, 2, 3];
+    'outer: loop {
+        while let Some(n) = pending.pop() {
+            yield noop();
+            if n == 2 {
+                break 'outer;
+            }
+            counter.0 += n;
+        }
+    }
+
+    let Some(&first) = pending.first() else {
+        yield noop();
+        return;
+    };
+    counter.0 += first * 10;
+
+    let bonus = 'bonus: {
+        if markers.is_empty() {
+            break 'bonus yield std::future::ready(100);
+        }
+        0
+    };
+    counter.0 += bonus;
+
+    commands.spawn(Marker);
+    yield next_frame();
+
+    // 遮蔽参数的绑定在 yield 后保持不变
+    let markers = markers.iter().count() as u32;
+    yield noop();
+    counter.0 += markers * 1000;
+}
+
+#[coroutine_system]
+fn scoped_shadowing(mut counter: ResMut<Counter>, markers: Query<&Marker>) {
+    // 遮蔽参数的作用域中经过了 yield，作用域结束后参数被重新获取
+    for counter in 0..2 {
+        yield std::future::ready(counter);
+    }
+    counter.0 += 1;
+
+    {
+        let markers = markers.iter().count();
+        yield noop();
+        counter.0 += markers as u32 * 10;
+    }
+    counter.0 += markers.iter().count() as u32 * 100;
+
+    if let Some(counter) = Some(1000) {
+        yield noop();
+        let _ = counter;
+    }
+    counter.0 += 1000;
+
+    match Some(2) {
+        Some(counter) => {
+            yield next_frame();
+            let _ = counter;
+        }
+        None => counter.0 += 1,
+    }
+    if counter.0 == 0 {
+        let markers = ();
+        yield noop();
+        return markers;
+    }
+    counter.0 += markers.iter().count() as u32 * 10_000;
+}
+
+#[derive(Component)]
+struct Player;
+
+#[derive(Component)]
+struct Level(u32);
+
+#[derive(Component)]
+struct Health(u32);
+
+#[derive(Resource)]
+struct Bonus(u32);
+
+/// 只能在主线程访问的资源，`Rc` 不是 `Send`
+struct Tag(Rc<Cell<u32>>);
+
+/// 每次恢复时看到的参数值
+#[derive(Resource, Default)]
+struct Seen(Vec<(u32, Option<u32>, u32, u32, u32)>);
+
+#[coroutine_system]
+fn varied_params(
+    player: Single<&Level, With<Player>>,
+    bonus: Option<Res<Bonus>>,
+    mut set: ParamSet<(Query<&mut Health>, Query<&Health>)>,
+    tag: NonSend<Tag>,
+    mut local: Local<u32>,
+    mut seen: ResMut<Seen>,
+) {
+    for _ in 0..2 {
+        **local += 1;
+        for mut health in set.p0().iter_mut() {
+            health.0 += 1;
+        }
+        let total = set.p1().iter().map(|health| health.0).sum();
+        seen.0.push((player.0, bonus.as_ref().map(|bonus| bonus.0), total, tag.0.get(), **local));
+        yield next_frame();
+    }
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.init_resource::<RunningCoroutines>();
+    world.init_resource::<Counter>();
+    world
+}
+
+#[test]
+fn params_are_refetched_after_yield() {
+    let mut world = world();
+
+    world.run_system_cached(count_markers).unwrap();
+    assert_eq!(world.resource::<Counter>().0, 0);
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(count_markers::id()),
+        Some(&CoroutineStatus::Running)
+    );
+
+    world.run_system_cached(count_markers).unwrap();
+    assert_eq!(world.resource::<Counter>().0, 110);
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(count_markers::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn coroutine_restarts_with_fresh_params() {
+    let mut world = world();
+
+    for _ in 0..4 {
+        world.run_system_cached(count_markers).unwrap();
+    }
+
+    // 第二次执行开始时已有 1 个实体，恢复时有 2 个
+    assert_eq!(world.resource::<Counter>().0, 110 + 1 + 20 + 100);
+}
+
+#[test]
+fn destructured_params_are_refetched_after_yield() {
+    let mut world = world();
+
+    world.run_system_cached(count_markers_destructured).unwrap();
+    world.run_system_cached(count_markers_destructured).unwrap();
+
+    assert_eq!(world.resource::<Counter>().0, 10);
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(count_markers_destructured::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn params_are_refetched_after_yield_in_expressions() {
+    let mut world = world();
+
+    world.run_system_cached(yield_in_expressions).unwrap();
+    world.run_system_cached(yield_in_expressions).unwrap();
+
+    assert_eq!(world.resource::<Counter>().0, 2 + 10);
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(yield_in_expressions::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn control_flow_is_preserved_across_yields() {
+    let mut world = world();
+
+    world.run_system_cached(control_flow).unwrap();
+    world.run_system_cached(control_flow).unwrap();
+
+    assert_eq!(world.resource::<Counter>().0, 3 + 10 + 100 + 1000);
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(control_flow::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn stale_input_cannot_access_params() {
+    let mut world = world();
+    let mut state = SystemState::<ResMut<Counter>>::new(&mut world);
+    let mut task = CoroutineTask::<ResMut<'static, Counter>>::default();
+
+    #[cfg(not(feature = "async-backend"))]
+    task.start(
+        #[coroutine]
+        |mut input: CoroutineTaskInput<ResMut<'static, Counter>>| {
+            input.params().0 += 1;
+            let mut next = yield into_coroutine_future(noop());
+
+            assert!(!input.is_valid());
+            next.params().0 += 1;
+            input.params().0 += 1;
+        },
+    );
+    #[cfg(feature = "async-backend")]
+    task.start(|yielder| async move {
+        let mut input = yielder.input();
+        input.params().0 += 1;
+        let mut next = yielder.yield_future(into_coroutine_future(noop())).await;
+
+        assert!(!input.is_valid());
+        next.params().0 += 1;
+        input.params().0 += 1;
+    });
+
+    let mut counter = state.get_mut(&mut world);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| task.step(&mut counter)));
+
+    assert!(result.is_err());
+    assert_eq!(counter.0, 2);
+}
+
+#[test]
+fn params_are_refetched_after_shadowing_scopes() {
+    let mut world = world();
+    world.spawn(Marker);
+
+    world.run_system_cached(scoped_shadowing).unwrap();
+    world.spawn(Marker);
+    world.run_system_cached(scoped_shadowing).unwrap();
+
+    assert_eq!(world.resource::<Counter>().0, 1 + 10 + 100 + 1000 + 20_000);
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(scoped_shadowing::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
+
+#[test]
+fn varied_params_are_refetched_after_yield() {
+    let mut world = world();
+    world.init_resource::<Seen>();
+    world.insert_non_send_resource(Tag(Rc::new(Cell::new(1))));
+    let player = world.spawn((Player, Level(1), Health(10))).id();
+    world.spawn(Health(20));
+
+    world.run_system_cached(varied_params).unwrap();
+    world.insert_resource(Bonus(5));
+    world.insert_non_send_resource(Tag(Rc::new(Cell::new(2))));
+    world.get_mut::<Level>(player).unwrap().0 = 2;
+    world.run_system_cached(varied_params).unwrap();
+    world.run_system_cached(varied_params).unwrap();
+
+    assert_eq!(
+        world.resource::<Seen>().0,
+        [(1, None, 32, 1, 1), (2, Some(5), 34, 2, 2)]
+    );
+    assert_eq!(
+        world.resource::<RunningCoroutines>().status(varied_params::id()),
+        Some(&CoroutineStatus::Completed)
+    );
+}
@@ -0,0 +1,85 @@
+//! stable Rust 上的 async 后端
+//!
+//! 其他测试使用 nightly 的 `#![feature(coroutines)]`，这个文件只使用 `coroutine_systems!`，
+//! 用 `cargo +stable test -p bevy_coroutine_system --features async-backend --test stable` 在 stable 上运行
+
+#![cfg(feature = "async-backend")]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::testing::CoroutineTestApp;
+use bevy_coroutine_system::CoroutineStatus;
+use std::time::Duration;
+
+#[derive(Resource, Default)]
+struct Counter(u32);
+
+#[derive(Component)]
+struct Marker;
+
+coroutine_systems! {
+    fn count_markers(mut counter: ResMut<Counter>, markers: Query<&Marker>, mut commands: Commands) {
+        counter.0 += markers.iter().count() as u32;
+        commands.spawn(Marker);
+
+        yield next_frame();
+
+        // 恢复后使用的是这一次运行获取的参数
+        counter.0 += markers.iter().count() as u32 * 10;
+        counter.0 += yield std::future::ready(100);
+    }
+
+    #[coroutine_system(retrigger = Ignore)]
+    fn wait_a_minute(mut counter: ResMut<Counter>) {
+        yield sleep(Duration::from_secs(60));
+        counter.0 += 1;
+    }
+
+    fn fail_after_a_frame(counter: Res<Counter>) -> Result<(), BevyError> {
+        yield next_frame();
+        if counter.0 == 0 {
+            return Err("counter is zero".into());
+        }
+        Ok(())
+    }
+}
+
+fn app() -> CoroutineTestApp {
+    let mut app = CoroutineTestApp::new();
+    app.init_resource::<Counter>();
+    app
+}
+
+#[test]
+fn params_are_refetched_after_yield() {
+    let mut app = app();
+    app.start(count_markers);
+    assert_eq!(app.world().resource::<Counter>().0, 0);
+
+    app.step_frames(1).assert_completed(count_markers::id());
+    assert_eq!(app.world().resource::<Counter>().0, 110);
+}
+
+#[test]
+fn sleep_follows_the_manual_clock() {
+    let mut app = app();
+    app.start(wait_a_minute).start(wait_a_minute);
+
+    app.advance_time(Duration::from_secs(59));
+    assert_eq!(app.world().resource::<Counter>().0, 0);
+
+    app.advance_time(Duration::from_secs(1)).assert_completed(wait_a_minute::id());
+    assert_eq!(app.world().resource::<Counter>().0, 1);
+}
+
+#[test]
+fn returned_errors_are_recorded() {
+    let mut app = app();
+    app.world_mut().resource_mut::<RunningCoroutines>().error_handler = bevy::ecs::error::ignore;
+    app.start(fail_after_a_frame).step_frames(1);
+
+    assert_eq!(
+        app.status(fail_after_a_frame::id()),
+        Some(&CoroutineStatus::Failed("counter is zero".into()))
+    );
+}
@@ -0,0 +1,40 @@
+//! 后台任务池上阻塞任务的并发限制
+//!
+//! 并发上限是全局的，这个文件中只有一个测试，避免和其他测试互相影响
+
+use bevy::tasks::{block_on, IoTaskPool, TaskPoolBuilder};
+use bevy_coroutine_system::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn blocking_tasks_respect_the_concurrency_limit() {
+    IoTaskPool::get_or_init(|| TaskPoolBuilder::new().num_threads(4).build());
+    BackgroundTaskPool::Io.set_max_blocking_tasks(2);
+    assert_eq!(BackgroundTaskPool::Io.max_blocking_tasks(), 2);
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..8)
+        .map(|i| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            spawn_blocking_task_on(BackgroundTaskPool::Io, move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = tasks.into_iter().map(|task| block_on(task).unwrap()).collect();
+    assert_eq!(results, (0..8).collect::<Vec<_>>());
+    // 线程比上限多，任务排队时同时执行的数量正好达到上限
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+
+    BackgroundTaskPool::Io.set_max_blocking_tasks(0);
+    assert_eq!(BackgroundTaskPool::Io.max_blocking_tasks(), 4);
+}
@@ -0,0 +1,67 @@
+//! 使用手动时钟测试协程
+
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::testing::CoroutineTestApp;
+use std::time::{Duration, Instant};
+
+#[derive(Resource, Default)]
+struct Door {
+    open: bool,
+    opened_after: Option<Duration>,
+}
+
+#[coroutine_system]
+fn open_door(mut door: ResMut<Door>, time: Res<Time>) {
+    let started = time.elapsed();
+    yield next_frame();
+    yield sleep(Duration::from_secs(60));
+    door.open = true;
+    door.opened_after = Some(time.elapsed() - started);
+}
+
+fn app() -> CoroutineTestApp {
+    let mut app = CoroutineTestApp::new();
+    app.init_resource::<Door>();
+    app
+}
+
+#[test]
+fn sleeping_coroutines_follow_the_manual_clock() {
+    let real_start = Instant::now();
+    let mut app = app();
+    app.start(open_door).assert_yield_count(open_door::id(), 1);
+
+    app.step_frames(1).assert_yield_count(open_door::id(), 2);
+    app.step_frames(10).assert_yield_count(open_door::id(), 2);
+    let waiting_on = app.info(open_door::id()).unwrap().waiting_on.unwrap();
+    assert!(waiting_on.future.ends_with("::Sleep"));
+
+    app.advance_time(Duration::from_secs(59));
+    assert!(!app.world().resource::<Door>().open);
+    // 运行时信息同样使用手动时钟
+    assert_eq!(app.info(open_door::id()).unwrap().elapsed(), Duration::from_secs(59));
+
+    app.advance_time(Duration::from_secs(1)).assert_completed(open_door::id());
+    let door = app.world().resource::<Door>();
+    assert!(door.open);
+    assert_eq!(door.opened_after, Some(Duration::from_secs(60)));
+    assert_eq!(app.elapsed(), Duration::from_secs(60));
+    assert!(real_start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+#[should_panic(expected = "has executed 1 yields, expected 2")]
+fn yield_count_assertion_reports_the_current_count() {
+    let mut app = app();
+    app.start(open_door).assert_yield_count(open_door::id(), 2);
+}
+
+#[test]
+#[should_panic(expected = "has not completed")]
+fn completed_assertion_fails_for_running_coroutines() {
+    let mut app = app();
+    app.start(open_door).assert_completed(open_door::id());
+}
@@ -0,0 +1,10 @@
+#![feature(coroutines)]
+
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn awaits_instead_of_yield() {
+    next_frame().await;
+}
+
+fn main() {}
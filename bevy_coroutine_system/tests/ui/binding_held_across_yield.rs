@@ -0,0 +1,18 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn spawn_later(mut commands: Commands, query: Query<(Entity, &mut Transform)>) {
+    if let Some((entity, transform)) = query.iter_mut().next() {
+        yield next_frame();
+        info!("{entity} moved to {}", transform.translation);
+    }
+
+    let mut entity = commands.spawn_empty();
+    yield next_frame();
+    entity.insert(Transform::default());
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn move_player(mut query: Query<&mut Transform>, player: Res<PlayerEntity>) {
+    let mut transform = query.get_mut(player.0).unwrap();
+    transform.translation.x += 1.0;
+
+    yield next_frame();
+
+    transform.translation.y += 1.0;
+}
+
+#[derive(Resource)]
+struct PlayerEntity(Entity);
+
+fn main() {}
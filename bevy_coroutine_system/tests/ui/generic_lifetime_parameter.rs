@@ -0,0 +1,12 @@
+#![feature(coroutines)]
+
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn despawn_after<'w, T: bevy::prelude::Component>(
+    query: bevy::prelude::Query<'w, 'w, bevy::prelude::Entity, bevy::prelude::With<T>>,
+) {
+    yield next_frame();
+}
+
+fn main() {}
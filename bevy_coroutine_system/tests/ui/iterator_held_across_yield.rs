@@ -0,0 +1,14 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn move_all(mut query: Query<&mut Transform>) {
+    for mut transform in query.iter_mut() {
+        transform.translation.x += 1.0;
+        yield next_frame();
+    }
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#![feature(coroutines)]
+
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn with_lifetime<'a>(query: bevy::prelude::Query<'a, 'a, &bevy::prelude::Transform>) {
+    yield next_frame();
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn blink() {
+    yield next_frame();
+}
+
+fn start(mut commands: Commands, player: Single<Entity, With<Player>>) {
+    // 组合出的系统不会运行在 `blink::id()` 下，实体无法拥有它
+    commands.entity(*player).start_coroutine(blink.map(drop));
+}
+
+#[derive(Component)]
+struct Player;
+
+fn main() {}
@@ -0,0 +1,21 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+
+#[derive(Resource)]
+struct Score(u32);
+
+#[coroutine_system]
+fn copy_before_yield(score: Option<Res<Score>>, mut query: Query<(Entity, &mut Transform)>) {
+    let value = score.as_ref().map(|s| s.0).unwrap_or_default();
+    let x = query.iter_mut().map(|(_, t)| t.translation.x as u32).next().unwrap_or_default();
+    let entities: Vec<Entity> = query.iter().map(|(entity, _)| entity).collect();
+    let first = query.iter_mut().next().map(|(entity, _)| entity);
+
+    yield next_frame();
+
+    info!("{value} {x} {entities:?} {first:?}");
+}
+
+fn main() {}
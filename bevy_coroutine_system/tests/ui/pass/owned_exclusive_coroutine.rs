@@ -0,0 +1,30 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+use bevy_coroutine_system::OwnedCoroutines;
+
+#[coroutine_system]
+fn blink(mut owners: Query<&OwnedCoroutines>) {
+    let owned = owners.iter_mut().filter(|owned| owned.contains(blink::id())).count();
+    yield next_frame();
+    info!("{owned}");
+}
+
+#[coroutine_system]
+fn rebuild(world: &mut World) {
+    world.flush();
+    yield next_frame();
+}
+
+fn start_owned(mut commands: Commands, player: Single<Entity, With<Player>>) {
+    commands.entity(*player).start_coroutine(blink).start_coroutine(rebuild);
+}
+
+#[derive(Component)]
+struct Player;
+
+fn main() {
+    let mut app = App::new();
+    app.add_systems(Update, start_owned);
+}
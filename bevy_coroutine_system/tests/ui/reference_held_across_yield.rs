@@ -0,0 +1,19 @@
+#![feature(coroutines)]
+
+use bevy::prelude::*;
+use bevy_coroutine_system::prelude::*;
+
+#[derive(Resource)]
+struct Score(u32);
+
+#[coroutine_system]
+fn count(mut score: ResMut<Score>) {
+    let value = &mut score.0;
+
+    loop {
+        *value += 1;
+        yield next_frame();
+    }
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#![feature(coroutines)]
+
+use bevy_coroutine_system::prelude::*;
+
+#[coroutine_system]
+fn world_and_commands(world: &mut bevy::prelude::World, commands: bevy::prelude::Commands) {
+    yield next_frame();
+}
+
+fn main() {}
@@ -0,0 +1,362 @@
+//! 检查跨越 yield 持有的系统参数借用
+//!
+//! 每次 yield 恢复后系统参数都会重新获取，跨越 yield 持有的借用指向的是上一次运行的参数。
+//! 这里在展开前找出常见的写法，给出指向借用和 yield 的错误，而不是生成代码上的借用检查错误。
+
+use crate::{collect_bindings, is_yield_macro};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Expr, Ident, Pat, Stmt};
+
+/// 返回值一定借用了接收者的方法
+/// 
+/// `get`、`single` 等只读方法的返回值可能不含借用（如 `Query<Entity>`），不在其中
+const BORROWING_METHODS: &[&str] = &[
+    "iter", "iter_mut", "iter_many", "iter_many_mut", "iter_combinations", "iter_combinations_mut",
+    "par_iter", "par_iter_mut", "get_mut", "get_many_mut", "single_mut", "get_single_mut", "read",
+    "spawn", "spawn_empty", "entity", "get_entity", "as_ref", "as_mut", "as_deref",
+    "as_deref_mut", "deref", "deref_mut", "p0", "p1", "p2", "p3", "p4", "p5", "p6", "p7",
+];
+
+/// 返回值一定保留了接收者中借用的方法，如不改变元素的迭代器适配器和 `unwrap`
+///
+/// `map`、`cloned`、`unwrap_or_default` 等可能产生不含借用的值，不在其中，交给借用检查处理
+const FORWARDING_METHODS: &[&str] = &[
+    "filter", "flatten", "enumerate", "zip", "chain", "rev", "skip", "take", "skip_while",
+    "take_while", "step_by", "peekable", "inspect", "by_ref", "into_iter", "unwrap", "expect", "ok",
+];
+
+/// 从迭代器中取出一项的方法
+const ITEM_METHODS: &[&str] = &["next", "next_back", "nth", "last", "find", "max_by_key", "min_by_key"];
+
+/// 检查函数体中跨越 yield 持有的参数借用，`params` 是参数绑定的变量
+pub(crate) fn check_held_borrows(block: &syn::Block, params: &[&Ident]) -> Option<syn::Error> {
+    let mut checker = HeldBorrowChecker {
+        params,
+        errors: Vec::new(),
+    };
+    checker.visit_block(block);
+    checker.errors.into_iter().reduce(|mut errors, error| {
+        errors.combine(error);
+        errors
+    })
+}
+
+struct HeldBorrowChecker<'a> {
+    params: &'a [&'a Ident],
+    errors: Vec<syn::Error>,
+}
+
+impl HeldBorrowChecker<'_> {
+    /// 表达式的值借用的参数
+    fn borrowed_param(&self, expr: &Expr) -> Option<Ident> {
+        self.borrowed_param_of(expr, false)
+    }
+
+    /// `item` 表示表达式取出的是迭代器或查询结果中的一项
+    fn borrowed_param_of(&self, expr: &Expr, item: bool) -> Option<Ident> {
+        match expr {
+            Expr::Reference(expr_ref) => self.param_root(&expr_ref.expr),
+            // 只读查询的结果可能不含借用（如 `Query<Entity>`），只有可变的结果和事件一定借用了参数
+            Expr::MethodCall(call) if is_method(call, BORROWING_METHODS) => {
+                let method = call.method.to_string();
+                if item && !method.ends_with("_mut") && method != "read" {
+                    return None;
+                }
+                self.param_root(&call.receiver)
+            }
+            Expr::MethodCall(call) if is_method(call, FORWARDING_METHODS) => {
+                self.borrowed_param_of(&call.receiver, item)
+            }
+            Expr::MethodCall(call) if is_method(call, ITEM_METHODS) => {
+                self.borrowed_param_of(&call.receiver, true)
+            }
+            Expr::Try(expr_try) => self.borrowed_param_of(&expr_try.expr, item),
+            Expr::Paren(expr_paren) => self.borrowed_param_of(&expr_paren.expr, item),
+            // 参数绑定本身就是参数的可变引用
+            Expr::Path(_) if !item => self.param_root(expr),
+            _ => None,
+        }
+    }
+
+    /// 位置表达式所在的参数
+    fn param_root(&self, expr: &Expr) -> Option<Ident> {
+        match expr {
+            Expr::Path(expr_path) => {
+                let ident = expr_path.path.get_ident()?;
+                self.params.iter().find(|param| **param == ident).map(|_| ident.clone())
+            }
+            Expr::MethodCall(call) => self.param_root(&call.receiver),
+            Expr::Field(field) => self.param_root(&field.base),
+            Expr::Index(index) => self.param_root(&index.expr),
+            Expr::Try(expr_try) => self.param_root(&expr_try.expr),
+            Expr::Paren(expr_paren) => self.param_root(&expr_paren.expr),
+            Expr::Reference(expr_ref) => self.param_root(&expr_ref.expr),
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => {
+                self.param_root(&unary.expr)
+            }
+            _ => None,
+        }
+    }
+
+    /// 检查绑定了参数借用的变量在 yield 之后是否还被使用
+    fn check_bindings<'s>(
+        &mut self,
+        borrow: &Expr,
+        pat: &Pat,
+        stmts: impl IntoIterator<Item = &'s Stmt>,
+    ) {
+        let Some(param) = self.borrowed_param(borrow) else {
+            return;
+        };
+        let mut names = Vec::new();
+        collect_bindings(pat, &mut names);
+        if names.is_empty() {
+            return;
+        }
+
+        let mut events = Events {
+            names: &names,
+            ..Events::default()
+        };
+        for stmt in stmts {
+            // 同名的绑定遮蔽了借用，之后的使用与它无关
+            if let Stmt::Local(local) = stmt {
+                if let Some(init) = &local.init {
+                    events.visit_local_init(init);
+                }
+                let mut shadowing = Vec::new();
+                collect_bindings(&local.pat, &mut shadowing);
+                if shadowing.iter().any(|ident| names.contains(ident)) {
+                    break;
+                }
+                continue;
+            }
+            events.visit_stmt(stmt);
+        }
+
+        if let Some((yield_span, use_span)) = events.held_across_yield() {
+            let name = names
+                .iter()
+                .find(|name| {
+                    events
+                        .events
+                        .iter()
+                        .any(|event| matches!(event, Event::Use(ident) if ident == **name))
+                })
+                .unwrap_or(&names[0]);
+            self.report(&param, borrow.span(), yield_span, use_span, name);
+        }
+    }
+
+    fn report(&mut self, param: &Ident, borrow: Span, yield_span: Span, use_span: Span, held: &Ident) {
+        self.errors.push(syn::Error::new(
+            borrow,
+            format!(
+                "this borrow of system parameter `{param}` is held across a yield; \
+                 parameters are fetched again after each yield, so `{held}` cannot be used after it"
+            ),
+        ));
+        self.errors.push(syn::Error::new(
+            yield_span,
+            format!(
+                "`{param}` is fetched again after this yield; copy the data you need before it, \
+                 or borrow `{param}` again after it"
+            ),
+        ));
+        self.errors.push(syn::Error::new(
+            use_span,
+            format!("`{held}` is used here after resuming from the yield"),
+        ));
+    }
+}
+
+impl<'ast> Visit<'ast> for HeldBorrowChecker<'_> {
+    fn visit_block(&mut self, block: &'ast syn::Block) {
+        for (index, stmt) in block.stmts.iter().enumerate() {
+            if let Stmt::Local(local) = stmt
+                && let Some(init) = &local.init
+            {
+                self.check_bindings(&init.expr, &local.pat, &block.stmts[index + 1..]);
+            }
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_expr_for_loop(&mut self, expr_for: &'ast syn::ExprForLoop) {
+        // 迭代器在整个循环中都被持有
+        if let Some(param) = self.borrowed_param(&expr_for.expr) {
+            let mut events = Events::default();
+            events.visit_block(&expr_for.body);
+            if let Some(yield_span) = events.first_yield() {
+                self.errors.push(syn::Error::new(
+                    expr_for.expr.span(),
+                    format!(
+                        "this loop iterates over a borrow of system parameter `{param}` \
+                         and yields inside its body; parameters are fetched again after each yield"
+                    ),
+                ));
+                self.errors.push(syn::Error::new(
+                    yield_span,
+                    format!(
+                        "`{param}` is fetched again after this yield; collect what you need \
+                         before the loop, or iterate again after the yield"
+                    ),
+                ));
+            }
+        }
+        visit::visit_expr_for_loop(self, expr_for);
+    }
+
+    fn visit_expr_if(&mut self, expr_if: &'ast syn::ExprIf) {
+        if let Expr::Let(expr_let) = &*expr_if.cond {
+            self.check_bindings(&expr_let.expr, &expr_let.pat, &expr_if.then_branch.stmts);
+        }
+        visit::visit_expr_if(self, expr_if);
+    }
+
+    fn visit_expr_match(&mut self, expr_match: &'ast syn::ExprMatch) {
+        for arm in &expr_match.arms {
+            let body = Stmt::Expr((*arm.body).clone(), None);
+            self.check_bindings(&expr_match.expr, &arm.pat, [&body]);
+        }
+        visit::visit_expr_match(self, expr_match);
+    }
+
+    // 闭包、async 块和内部的函数不属于这个协程
+    fn visit_expr_closure(&mut self, _closure: &'ast syn::ExprClosure) {}
+
+    fn visit_expr_async(&mut self, _async_block: &'ast syn::ExprAsync) {}
+
+    fn visit_item(&mut self, _item: &'ast syn::Item) {}
+}
+
+fn is_method(call: &syn::ExprMethodCall, methods: &[&str]) -> bool {
+    methods.iter().any(|method| call.method == method)
+}
+
+/// 按代码顺序记录的 yield 和变量的使用
+enum Event {
+    Yield(Span),
+    Use(Ident),
+}
+
+#[derive(Default)]
+struct Events<'a> {
+    names: &'a [&'a Ident],
+    events: Vec<Event>,
+    /// 循环体中 yield 之前的使用，在下一次迭代时同样位于 yield 之后
+    looped: Option<(Span, Span)>,
+    in_closure: bool,
+}
+
+impl Events<'_> {
+    fn first_yield(&self) -> Option<Span> {
+        self.events.iter().find_map(|event| match event {
+            Event::Yield(span) => Some(*span),
+            Event::Use(_) => None,
+        })
+    }
+
+    /// 第一个 yield 和它之后第一次使用的位置
+    fn held_across_yield(&self) -> Option<(Span, Span)> {
+        let yield_span = self.first_yield()?;
+        let after_yield = self
+            .events
+            .iter()
+            .skip_while(|event| matches!(event, Event::Use(_)))
+            .find_map(|event| match event {
+                Event::Use(ident) => Some(ident.span()),
+                Event::Yield(_) => None,
+            });
+        after_yield.map(|use_span| (yield_span, use_span)).or(self.looped)
+    }
+
+    fn use_tokens(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) if self.names.iter().any(|name| **name == ident) => {
+                    self.events.push(Event::Use(ident));
+                }
+                TokenTree::Group(group) => self.use_tokens(group.stream()),
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_local_init(&mut self, init: &syn::LocalInit) {
+        self.visit_expr(&init.expr);
+        if let Some((_, diverge)) = &init.diverge {
+            self.visit_expr(diverge);
+        }
+    }
+
+    /// 记录循环体中的事件，循环体同时有 yield 和使用时借用一定跨越了 yield
+    fn visit_loop_body(&mut self, body: &syn::Block) {
+        let mut events = Events {
+            names: self.names,
+            in_closure: self.in_closure,
+            ..Events::default()
+        };
+        events.visit_block(body);
+        if self.looped.is_none() {
+            self.looped = events.looped.or_else(|| {
+                let yield_span = events.first_yield()?;
+                events.events.iter().find_map(|event| match event {
+                    Event::Use(ident) => Some((yield_span, ident.span())),
+                    Event::Yield(_) => None,
+                })
+            });
+        }
+        self.events.append(&mut events.events);
+    }
+}
+
+impl<'ast> Visit<'ast> for Events<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Yield(expr_yield) if !self.in_closure => {
+                visit::visit_expr_yield(self, expr_yield);
+                self.events.push(Event::Yield(expr_yield.span()));
+            }
+            Expr::Macro(mac_expr) if !self.in_closure && is_yield_macro(&mac_expr.mac) => {
+                self.use_tokens(mac_expr.mac.tokens.clone());
+                self.events.push(Event::Yield(mac_expr.span()));
+            }
+            Expr::Macro(mac_expr) => self.use_tokens(mac_expr.mac.tokens.clone()),
+            Expr::Path(expr_path) => {
+                if let Some(ident) = expr_path.path.get_ident()
+                    && self.names.contains(&ident)
+                {
+                    self.events.push(Event::Use(ident.clone()));
+                }
+            }
+            Expr::Loop(expr_loop) => self.visit_loop_body(&expr_loop.body),
+            Expr::While(expr_while) => {
+                self.visit_expr(&expr_while.cond);
+                self.visit_loop_body(&expr_while.body);
+            }
+            Expr::ForLoop(expr_for) => {
+                self.visit_expr(&expr_for.expr);
+                self.visit_loop_body(&expr_for.body);
+            }
+            // 闭包可能捕获变量，但其中的 yield 不属于这个协程
+            Expr::Closure(_) | Expr::Async(_) => {
+                let in_closure = std::mem::replace(&mut self.in_closure, true);
+                visit::visit_expr(self, expr);
+                self.in_closure = in_closure;
+            }
+            _ => visit::visit_expr(self, expr),
+        }
+    }
+
+    fn visit_stmt_macro(&mut self, stmt_mac: &'ast syn::StmtMacro) {
+        self.use_tokens(stmt_mac.mac.tokens.clone());
+        if !self.in_closure && is_yield_macro(&stmt_mac.mac) {
+            self.events.push(Event::Yield(stmt_mac.span()));
+        }
+    }
+
+    fn visit_item(&mut self, _item: &'ast syn::Item) {}
+}
@@ -13,9 +13,14 @@
 //! }
 //! ```
 
+mod borrows;
+mod options;
+
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
-use syn::{parse_macro_input, FnArg, ItemFn, Pat, ReturnType};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_macro_input, FnArg, ItemFn, Pat};
 
 /// 协程系统的过程宏
 /// 
@@ -41,43 +46,166 @@ use syn::{parse_macro_input, FnArg, ItemFn, Pat, ReturnType};
 /// }
 /// ```
 /// 
+/// `yield` 可以出现在任何表达式中，如 `foo(yield x)`、`if yield check() {}`、
+/// `let n = 1 + yield y`，每次恢复后都会重新获取参数。循环标签、`while let`、`let ... else`、
+/// `unsafe` 块等都保持原样；闭包和 `async` 块中的代码不会被转换。
+/// 
+/// 每次 yield 恢复后参数都会重新获取，所以参数的借用（如 `query.iter_mut()` 的迭代器、
+/// `query.get_mut(entity)` 得到的组件、`commands.spawn(...)` 得到的 `EntityCommands`）
+/// 不能跨越 yield 持有，宏会在编译期报告这些借用和对应的 yield。
+/// 
 /// # 支持的参数类型
 /// 
-/// - 所有实现了 `SystemParam` 的类型
-/// - 包括但不限于：`Commands`, `Query`, `Res`, `ResMut`, `Local` 等
+/// - 所有实现了 `SystemParam` 的类型，与类型的写法无关
+/// - 包括但不限于：`Commands`, `Query`, `Res`, `ResMut`, `Local`, `Single`, `Option<Res<T>>`,
+///   `NonSend`, `ParamSet`, `Gizmos`, `Populated`、自定义的 `#[derive(SystemParam)]` 结构体和类型别名
+/// - 参数的生命周期可以省略，由 Bevy 像普通系统一样推导
+/// - 参数可以使用解构模式和 `_`，如 `(time, mut score): (Res<Time>, ResMut<Score>)`，
+///   解构出的绑定是各个字段的可变引用，每次 yield 恢复后重新绑定
+/// - 最多 15 个参数，更多的参数可以组合成元组或自定义的 `SystemParam`
+/// - 唯一的参数是 `&mut World` 时生成独占系统，协程体可以任意访问 `World`（同步生成场景、运行调度等），
+///   推进期间 `RunningCoroutines` 不被借用：
+/// 
+/// ```rust,ignore
+/// #[coroutine_system]
+/// fn load_scene(world: &mut World) {
+///     world.spawn(SceneRoot(handle));
+///     yield next_frame();
+///     world.run_schedule(PostLoad);
+/// }
+/// ```
+/// 
+/// # 返回值
+/// 
+/// 函数可以返回 `()` 或 `Result<(), E>`（`E` 可以转换为 `BevyError`）。
+/// 返回 `Result` 时 `?` 可以跨越 yield 使用，错误会交给 `RunningCoroutines::error_handler` 处理，
+/// 并记录在协程的运行状态中。协程体中的 panic 会被捕获并记录为失败，不会影响其他系统：
+/// 
+/// ```rust,ignore
+/// #[coroutine_system]
+/// fn load_level(mut commands: Commands) -> Result<(), BevyError> {
+///     let text = yield spawn_blocking_task(|| std::fs::read_to_string("level.ron"));
+///     let level: Level = ron::from_str(&text??)?;
+///     commands.spawn(level);
+///     Ok(())
+/// }
+/// ```
+/// 
+/// # 泛型
+/// 
+/// 函数可以带有类型参数、常量参数和 `where` 子句，每个泛型实例都是独立的协程系统，
+/// 通过 `id::<T>()` 获取各自的标识符：
+/// 
+/// ```rust,ignore
+/// #[coroutine_system]
+/// fn despawn_after<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+///     yield sleep(Duration::from_secs(1));
+///     for entity in query.iter() {
+///         commands.entity(entity).despawn();
+///     }
+/// }
+/// 
+/// app.add_coroutine(despawn_after::<Enemy>);
+/// ```
+/// 
+/// # 宏参数
+/// 
+/// - `name = "..."`：协程标识符的名称，默认为 `concat!(module_path!(), "::", 函数名)`
+/// - `schedule = Label`：协程被唤醒后在哪个调度中恢复，默认为 `Update`
+/// - `retrigger = Resume | Ignore | Restart`：协程运行中再次被触发时立即恢复（默认）、忽略或重新开始
+/// - `repeat`：协程成功结束后在下一帧自动重新开始
+/// - `state = State::Variant`：离开该状态时取消协程
+/// - `tags = ["...", ...]`：协程的标签，可以通过 `RunningCoroutines::tagged` 查找
+/// - `priority = N`：同一调度中被唤醒的协程按优先级从高到低恢复，优先级相同时按登记顺序，默认为 0
+/// 
+/// ```rust,ignore
+/// #[coroutine_system(name = "intro", retrigger = Ignore, state = GameState::Menu, tags = ["ui"])]
+/// fn intro(mut commands: Commands) {
+///     yield sleep(Duration::from_secs(1));
+///     commands.spawn(Title);
+/// }
+/// ```
+/// 
+/// 生成的 `config()` 函数返回这些参数对应的 `CoroutineConfig`。
 /// 
 /// # 限制
 /// 
-/// - 函数必须返回 `()` (unit type)
-/// - 需要 Rust nightly 版本和相应的 feature flags
+/// - 需要 Rust nightly 版本和相应的 feature flags，stable Rust 需要启用 `async-backend` 并使用 [`coroutine_systems!`]
+/// - 不支持生命周期参数
+/// - 在内部作用域中被遮蔽的参数不会在该作用域中的 yield 后重新获取，作用域结束后也不能再使用
 #[proc_macro_attribute]
-pub fn coroutine_system(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn coroutine_system(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let options = parse_macro_input!(attr as options::CoroutineOptions);
     let input_fn = parse_macro_input!(item as ItemFn);
-    
+    expand_coroutine_system(&options, &input_fn).into()
+}
+
+/// 以函数宏的形式定义协程系统
+/// 
+/// 与 [`macro@coroutine_system`] 相同，可以一次定义多个协程系统，参数写在函数的
+/// `#[coroutine_system(...)]` 属性中。宏的输入不会被编译器当作 Rust 代码解析，
+/// 所以协程体中的 `yield` 不需要 `#![feature(coroutines)]`，配合 `async-backend` feature 可以在 stable Rust 上使用：
+/// 
+/// ```rust,ignore
+/// coroutine_systems! {
+///     fn fade_in(mut sprites: Query<&mut Sprite>) {
+///         for step in 0..10 {
+///             for mut sprite in sprites.iter_mut() {
+///                 sprite.color.set_alpha(step as f32 / 10.0);
+///             }
+///             yield next_frame();
+///         }
+///     }
+///     
+///     #[coroutine_system(retrigger = Ignore)]
+///     fn shake(mut camera: Single<&mut Transform, With<Camera>>) {
+///         camera.translation.x += 5.0;
+///         yield sleep(Duration::from_millis(100));
+///         camera.translation.x -= 5.0;
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn coroutine_systems(input: TokenStream) -> TokenStream {
+    let items = parse_macro_input!(input as options::CoroutineItems);
+    items
+        .0
+        .iter()
+        .map(|(options, input_fn)| expand_coroutine_system(options, input_fn))
+        .collect::<proc_macro2::TokenStream>()
+        .into()
+}
+
+/// 将一个函数展开为协程系统
+fn expand_coroutine_system(options: &options::CoroutineOptions, input_fn: &ItemFn) -> proc_macro2::TokenStream {
     // 解析函数信息
     let fn_name = &input_fn.sig.ident;
     let fn_vis = &input_fn.vis;
-    let _fn_generics = &input_fn.sig.generics;
+    let fn_generics = &input_fn.sig.generics;
     let fn_block = &input_fn.block;
     
-    // 检查返回类型（必须是unit）
-    match &input_fn.sig.output {
-        ReturnType::Default => {},
-        _ => {
-            return syn::Error::new_spanned(
-                &input_fn.sig.output,
-                "coroutine_system functions must not have a return type"
-            )
-            .to_compile_error()
-            .into();
-        }
+    // 泛型参数：生命周期参数无法用于 'static 的系统参数
+    if let Some(lifetime) = fn_generics.lifetimes().next() {
+        return syn::Error::new_spanned(
+            lifetime,
+            "coroutine_system functions cannot have lifetime parameters"
+        )
+        .to_compile_error();
     }
+    let generic_params: Vec<_> = fn_generics.params.iter().collect();
+    let generic_args: Vec<_> = fn_generics.params.iter().map(generic_param_ident).collect();
+    let where_clause = &fn_generics.where_clause;
+    
+    // 返回类型（`()` 或 `Result<(), E>`），由协程闭包原样使用
+    // 没有声明返回类型时显式标注 `-> ()`，永不返回的协程体（如无限循环）才能确定返回类型
+    let fn_output = match &input_fn.sig.output {
+        syn::ReturnType::Default => quote! { -> () },
+        output => quote! { #output },
+    };
     
     // 收集SystemParam参数
-    let mut params = Vec::new();
-    let mut param_names = Vec::new();
-    let mut param_types: Vec<syn::Type> = Vec::new();
-    let mut lifetime_req = LifetimeRequirement::none();
+    let mut param_pats = Vec::new();
+    let mut param_types = Vec::new();
     
     for arg in &input_fn.sig.inputs {
         match arg {
@@ -86,629 +214,676 @@ pub fn coroutine_system(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     arg,
                     "coroutine_system functions cannot have self parameters"
                 )
-                .to_compile_error()
-                .into();
+                .to_compile_error();
             }
             FnArg::Typed(pat_type) => {
-                params.push(pat_type);
+                // 参数的模式在每次获取参数时重新绑定
+                param_pats.push(param_binding_pattern(&pat_type.pat));
                 
-                // 提取参数名
-                if let Pat::Ident(pat_ident) = &*pat_type.pat {
-                    param_names.push(&pat_ident.ident);
-                } else {
-                    return syn::Error::new_spanned(
-                        &pat_type.pat,
-                        "coroutine_system only supports simple parameter patterns"
-                    )
-                    .to_compile_error()
-                    .into();
-                }
-                
-                // 分析生命周期需求
-                lifetime_req.merge(analyze_lifetime_requirements(&pat_type.ty));
-                
-                // 提取参数类型并添加生命周期（如果需要）
-                let ty = add_lifetimes_to_type(&pat_type.ty);
-                param_types.push(ty);
+                // 参数类型保持原样，省略的生命周期由 Bevy 像普通系统一样推导
+                param_types.push(&*pat_type.ty);
             }
         }
     }
     
-    // 生成SystemParam组合结构名（转换为CamelCase）
-    let struct_name_str = format!("{}Params", fn_name);
-    let struct_name_str = struct_name_str.chars().enumerate().map(|(i, c)| {
-        if i == 0 || struct_name_str.chars().nth(i - 1).unwrap() == '_' {
-            c.to_uppercase().collect::<String>()
-        } else if c == '_' {
-            String::new()
-        } else {
-            c.to_string()
-        }
-    }).collect::<String>();
-    let params_struct_name = format_ident!("{}", struct_name_str);
+    // Bevy 的系统最多有 16 个参数，其中一个用于协程自身的状态
+    if param_types.len() > MAX_PARAMS {
+        return syn::Error::new_spanned(
+            &input_fn.sig.inputs,
+            format!(
+                "coroutine_system functions can have at most {MAX_PARAMS} parameters, \
+                 group them into tuples or a #[derive(SystemParam)] struct"
+            )
+        )
+        .to_compile_error();
+    }
+    
+    // 唯一的参数是 `&mut World` 时生成独占系统
+    let world_params = param_types.iter().filter(|ty| is_world_mut(ty)).count();
+    let exclusive = world_params == 1 && param_types.len() == 1;
+    if world_params > 0 && !exclusive {
+        return syn::Error::new_spanned(
+            &input_fn.sig.inputs,
+            "exclusive coroutine_system functions can only take a single `&mut World` parameter"
+        )
+        .to_compile_error();
+    }
+    
+    // 跨越 yield 持有的参数借用在展开前报错，错误指向借用和 yield
+    let mut bindings = Vec::new();
+    for pat in &param_pats {
+        collect_bindings(pat, &mut bindings);
+    }
+    let held_borrows = borrows::check_held_borrows(fn_block, &bindings);
+    
+    let param_paths: Vec<_> = (0..param_types.len()).map(syn::Index::from).collect();
+    let param_names: Vec<_> = (0..param_types.len())
+        .map(|index| format_ident!("__param_{}", index))
+        .collect();
+    
+    let signature_fn = if generic_args.is_empty() {
+        quote! { __signature }
+    } else {
+        quote! { __signature::<#(#generic_args),*> }
+    };
+    let (id_call, config_call, runner_call) = if generic_args.is_empty() {
+        (
+            quote! { #fn_name::id() },
+            quote! { #fn_name::config() },
+            quote! { #fn_name::runner() },
+        )
+    } else {
+        (
+            quote! { #fn_name::id::<#(#generic_args),*>() },
+            quote! { #fn_name::config::<#(#generic_args),*>() },
+            quote! { #fn_name::runner::<#(#generic_args),*>() },
+        )
+    };
     
-    // 根据生命周期需求生成结构体
-    // 注意：Bevy的SystemParam总是需要'w和's，即使未使用也需要保留
-    let params_struct = if lifetime_req.needs_w && lifetime_req.needs_s {
-        // 两个生命周期都被使用
+    // 转换函数体，处理yield表达式
+    let transformed_body = transform_function_body(fn_block, &param_pats, &param_paths);
+    
+    // 协程体：nightly 的协程闭包，或 async 后端中立即调用的 async 闭包返回的 Future
+    // async 闭包用来保留函数的返回类型，使 `?` 和 `return` 的类型与函数签名一致
+    let start_coroutine = if cfg!(feature = "async-backend") {
         quote! {
-            #[derive(::bevy::ecs::system::SystemParam)]
-            #[allow(dead_code)]
-            struct #params_struct_name<'w, 's> {
-                #(#param_names: #param_types,)*
-            }
+            move |__yielder| (async move || #fn_output {
+                // 移入闭包体，使返回的 Future 拥有它而不是借用闭包
+                let __yielder = __yielder;
+                let mut __coroutine_input = ::bevy_coroutine_system::CoroutineTaskInput::typed(
+                    __yielder.input(),
+                    __input_type,
+                );
+                #transformed_body
+            })()
         }
     } else {
-        // 至少有一个生命周期未被使用，需要添加PhantomData
-        let phantom_type = if !lifetime_req.needs_w && !lifetime_req.needs_s {
-            // 两个都未使用
-            quote! { ::std::marker::PhantomData<(&'w (), &'s ())> }
-        } else if !lifetime_req.needs_w {
-            // 只有'w未使用
-            quote! { ::std::marker::PhantomData<&'w ()> }
-        } else {
-            // 只有's未使用
-            quote! { ::std::marker::PhantomData<&'s ()> }
-        };
-        
         quote! {
-            #[derive(::bevy::ecs::system::SystemParam)]
-            struct #params_struct_name<'w, 's> {
-                #(#param_names: #param_types,)*
-                _phantom: #phantom_type,
+            #[coroutine]
+            move |__coroutine_input| #fn_output {
+                let mut __coroutine_input = ::bevy_coroutine_system::CoroutineTaskInput::typed(
+                    __coroutine_input,
+                    __input_type,
+                );
+                #transformed_body
             }
         }
     };
     
-    // 转换函数体，处理yield表达式
-    let transformed_body = transform_function_body(fn_block, &param_names, &params_struct_name);
-    
-    // 生成包装函数（确保只使用<'w, 's>生命周期）
-    let wrapper_fn = quote! {
-        #[allow(unused_variables)]
-        #fn_vis fn #fn_name<'w, 's>(
-            params: #params_struct_name<'w, 's>,
-            mut __task: ::bevy::prelude::Local<
-                ::bevy_coroutine_system::CoroutineTask<
-                    ::bevy_coroutine_system::CoroutineTaskInput<#params_struct_name<'static, 'static>>
-                >
-            >,
-            mut __running_task: ::bevy::prelude::ResMut<::bevy_coroutine_system::RunningCoroutines>,
-        ) {
-            use ::std::ops::Coroutine;
-            use ::std::pin::Pin;
-            use ::std::ptr::NonNull;
-            use ::std::task::{Context, Poll, Waker};
-            
-            // 初始化协程
-            if __task.coroutine.is_none() {
-                __task.coroutine = Some(Box::pin(
-                    #[coroutine]
-                    move |mut __coroutine_input: ::bevy_coroutine_system::CoroutineTaskInput<#params_struct_name<'static, 'static>>| {
-                        #transformed_body
+    // 生成包装函数
+    // 参数类型保持原样，省略的生命周期由 Bevy 像普通系统一样推导
+    let wrapper_fn = if exclusive {
+        // 独占 World 的协程由独占系统推进，推进期间 World 不被 RunningCoroutines 的借用占用
+        quote! {
+            #[allow(unused_variables, unused_mut, clippy::redundant_closure_call, clippy::unused_unit)]
+            #fn_vis fn #fn_name<#(#generic_params),*>(
+                #(#param_names: #param_types,)*
+                mut __coroutine: ::bevy_coroutine_system::ExclusiveCoroutineState<'_>,
+            ) #where_clause {
+                // 独占系统运行期间 Bevy 把 `last_change_tick` 设为这个系统上一次运行的 tick
+                let __last_run = __param_0.last_change_tick();
+                let __task = __coroutine.exclusive_task();
+                
+                // 按协程的状态和配置决定这次运行如何处理协程
+                let __trigger = __param_0
+                    .resource_mut::<::bevy_coroutine_system::RunningCoroutines>()
+                    .trigger(#id_call, __task);
+                match __trigger {
+                    ::bevy_coroutine_system::CoroutineTrigger::Skip => return,
+                    ::bevy_coroutine_system::CoroutineTrigger::Resume => {}
+                    ::bevy_coroutine_system::CoroutineTrigger::Start => {
+                        let __input_type = __task.input_type();
+                        __task.start(#start_coroutine);
+                        
+                        __param_0
+                            .resource_mut::<::bevy_coroutine_system::RunningCoroutines>()
+                            .insert(#id_call, __task, #config_call, #runner_call);
                     }
-                ));
+                }
                 
-                __running_task.systems.insert(#fn_name::id(), ());
+                ::bevy_coroutine_system::RunningCoroutines::resume_exclusive(__param_0, #id_call, __task, __last_run);
             }
+        }
+    } else {
+        quote! {
+            #[allow(unused_variables, unused_mut, clippy::too_many_arguments, clippy::redundant_closure_call, clippy::unused_unit)]
+            #fn_vis fn #fn_name<#(#generic_params),*>(
+                #(#param_names: #param_types,)*
+                mut __coroutine: ::bevy_coroutine_system::CoroutineSystemState,
+            ) #where_clause {
+                // 与协程系统参数相同的函数签名，用来推导 'static 的系统参数类型
+                #[allow(clippy::too_many_arguments)]
+                fn __signature<#(#generic_params),*>(#(_: #param_types),*) #where_clause {}
+            
+                let __task = __coroutine.task.task(&#signature_fn);
             
-            // 循环处理，直到遇到 pending 的 async 操作或协程完成
-            loop {
-                // 处理异步结果
-                let mut async_result = None;
+                // 按协程的状态和配置决定这次运行如何处理协程
+                match __coroutine.running.trigger(#id_call, __task) {
+                    ::bevy_coroutine_system::CoroutineTrigger::Skip => return,
+                    ::bevy_coroutine_system::CoroutineTrigger::Resume => {}
+                    ::bevy_coroutine_system::CoroutineTrigger::Start => {
+                        let __input_type = __task.input_type();
+                        __task.start(#start_coroutine);
                 
-                if let Some(fut) = &mut __task.fut {
-                    let waker = Waker::noop();
-                    let mut cx = Context::from_waker(&waker);
-                    match fut.as_mut().poll(&mut cx) {
-                        Poll::Ready(v) => {
-                            async_result = Some(v);
-                            __task.fut = None;
-                        }
-                        Poll::Pending => {
-                            // async 操作未完成，退出循环等待下一帧
-                            return;
-                        }
+                        __coroutine.running.insert(#id_call, __task, #config_call, #runner_call);
                     }
                 }
-                
-                // 创建输入
-                let __coroutine_input = ::bevy_coroutine_system::CoroutineTaskInput {
-                    data_ptr: Some(unsafe { NonNull::new_unchecked(&params as *const _ as *mut _) }),
-                    async_result,
+            
+                let mut __params = (#(#param_names,)*);
+                // SAFETY: `__params` 由 Bevy 为 `__signature` 的参数类型获取的值组成，
+                // 在协程推进结束前不会再被直接访问
+                let __params = unsafe {
+                    ::bevy_coroutine_system::signature_params(&#signature_fn, &mut __params as *mut _ as *mut ())
                 };
-                
-                // 恢复协程
-                if let Some(coroutine) = &mut __task.coroutine {
-                    match coroutine.as_mut().resume(__coroutine_input) {
-                        ::std::ops::CoroutineState::Yielded(output) => {
-                            __task.fut = Some(output);
-                            // 继续循环，检查新 yield 的 future 是否立即完成
-                        }
-                        ::std::ops::CoroutineState::Complete(()) => {
-                            __task.coroutine = None;
-                            __task.fut = None;
-                            __running_task.systems.remove(#fn_name::id());
-                            return;
-                        }
-                    }
-                } else {
-                    // 没有协程了，退出循环
-                    break;
-                }
+            
+                // 推进协程，直到遇到 pending 的 async 操作或协程完成
+                // 协程结束或 panic 时由 RunningCoroutines 记录结果并清理
+                let __last_run = __coroutine.ticks.last_run();
+                __coroutine.running.resume(#id_call, __task, __last_run, __params);
             }
         }
     };
     
-    // 生成模块和ID函数
+    // 生成模块、ID函数和配置函数
     let fn_name_str = fn_name.to_string();
-    let id_fn = quote! {
-        pub mod #fn_name {
-            /// 获取协程系统的唯一标识符
-            /// 
-            /// 返回格式为 "module_path::function_name" 的唯一标识符
-            pub fn id() -> &'static str {
-                // 使用一个独特的常量来避免函数名重复
-                const ID: &str = concat!(module_path!(), "::", #fn_name_str);
-                ID
+    let base_id = match &options.name {
+        Some(name) => quote! { #name },
+        None => quote! { concat!(module_path!(), "::", #fn_name_str) },
+    };
+    let config = options.config();
+    let id_fn = if generic_args.is_empty() {
+        quote! {
+            pub mod #fn_name {
+                #[allow(unused_imports)]
+                use super::*;
+                
+                /// 获取协程系统的唯一标识符
+                /// 
+                /// 由协程系统函数的类型得到，名称为 "module_path::function_name" 或宏参数中指定的 `name`
+                pub fn id() -> ::bevy_coroutine_system::CoroutineId {
+                    ::bevy_coroutine_system::CoroutineId::of(&super::#fn_name, #base_id)
+                }
+                
+                /// 协程系统的配置，由宏参数生成
+                pub fn config() -> ::bevy_coroutine_system::CoroutineConfig {
+                    #config
+                }
+                
+                /// 重新运行协程系统的函数，驱动系统用它恢复被唤醒的协程
+                pub fn runner() -> ::bevy_coroutine_system::CoroutineRunner {
+                    |world| {
+                        world.run_system_cached(super::#fn_name).is_ok()
+                    }
+                }
+            }
+        }
+    } else {
+        let arg_names = fn_generics.params.iter().map(|param| match param {
+            syn::GenericParam::Const(param) => {
+                let ident = &param.ident;
+                quote! { ::std::string::ToString::to_string(&#ident) }
+            }
+            param => {
+                let ident = generic_param_ident(param);
+                quote! { ::std::string::ToString::to_string(::std::any::type_name::<#ident>()) }
+            }
+        });
+        quote! {
+            pub mod #fn_name {
+                #[allow(unused_imports)]
+                use super::*;
+                
+                /// 获取协程系统的唯一标识符
+                /// 
+                /// 每个泛型实例的标识符各不相同，名称为 "module_path::function_name<A, B>"
+                pub fn id<#(#generic_params),*>() -> ::bevy_coroutine_system::CoroutineId #where_clause {
+                    let system = super::#fn_name::<#(#generic_args),*>;
+                    ::bevy_coroutine_system::CoroutineId::of(
+                        &system,
+                        ::bevy_coroutine_system::generic_coroutine_name(
+                            &system,
+                            #base_id,
+                            || [#(#arg_names),*].join(", "),
+                        ),
+                    )
+                }
+                
+                /// 协程系统的配置，由宏参数生成
+                pub fn config<#(#generic_params),*>() -> ::bevy_coroutine_system::CoroutineConfig #where_clause {
+                    #config
+                }
+                
+                /// 重新运行协程系统的函数，驱动系统用它恢复被唤醒的协程
+                pub fn runner<#(#generic_params),*>() -> ::bevy_coroutine_system::CoroutineRunner #where_clause {
+                    |world| {
+                        world.run_system_cached(super::#fn_name::<#(#generic_args),*>).is_ok()
+                    }
+                }
             }
         }
     };
     
+    // 有跨越 yield 的借用时只生成参数相同的空系统，避免在生成的代码上产生更多错误
+    if let Some(error) = held_borrows {
+        let error = error.to_compile_error();
+        let output = quote! {
+            #error
+            
+            #[allow(unused_variables, clippy::too_many_arguments)]
+            #fn_vis fn #fn_name<#(#generic_params),*>(#(_: #param_types),*) #where_clause {}
+            
+            #id_fn
+        };
+        return output;
+    }
+    
     // 组合输出
     let output = quote! {
-        #params_struct
-        
         #wrapper_fn
         
         #id_fn
     };
     
-    output.into()
+    output
+}
+
+/// 参数类型是否为 `&mut World`
+fn is_world_mut(ty: &syn::Type) -> bool {
+    let syn::Type::Reference(reference) = ty else {
+        return false;
+    };
+    let syn::Type::Path(path) = &*reference.elem else {
+        return false;
+    };
+    reference.mutability.is_some()
+        && path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "World" && segment.arguments.is_none())
+}
+
+/// 泛型参数的名称
+fn generic_param_ident(param: &syn::GenericParam) -> &syn::Ident {
+    match param {
+        syn::GenericParam::Type(param) => &param.ident,
+        syn::GenericParam::Const(param) => &param.ident,
+        syn::GenericParam::Lifetime(param) => &param.lifetime.ident,
+    }
+}
+
+/// 将参数的模式转换为绑定到参数可变引用的模式
+/// 
+/// 参数以 `&mut` 的形式重新绑定，解构模式通过默认绑定模式得到各个字段的引用，
+/// 因此其中的 `mut`、`ref` 和 `ref mut` 需要去掉。单个标识符的模式保持原样
+fn param_binding_pattern(pat: &Pat) -> Pat {
+    let mut pat = pat.clone();
+    match &pat {
+        Pat::Ident(pat_ident) if pat_ident.subpat.is_none() => {}
+        _ => strip_binding_modes(&mut pat),
+    }
+    pat
+}
+
+/// 递归去掉模式中标识符的绑定方式
+fn strip_binding_modes(pat: &mut Pat) {
+    match pat {
+        Pat::Ident(pat_ident) => {
+            pat_ident.by_ref = None;
+            pat_ident.mutability = None;
+            if let Some((_, subpat)) = &mut pat_ident.subpat {
+                strip_binding_modes(subpat);
+            }
+        }
+        Pat::Tuple(pat_tuple) => pat_tuple.elems.iter_mut().for_each(strip_binding_modes),
+        Pat::TupleStruct(pat_tuple_struct) => {
+            pat_tuple_struct.elems.iter_mut().for_each(strip_binding_modes)
+        }
+        Pat::Struct(pat_struct) => pat_struct
+            .fields
+            .iter_mut()
+            .for_each(|field| strip_binding_modes(&mut field.pat)),
+        Pat::Slice(pat_slice) => pat_slice.elems.iter_mut().for_each(strip_binding_modes),
+        Pat::Or(pat_or) => pat_or.cases.iter_mut().for_each(strip_binding_modes),
+        Pat::Paren(pat_paren) => strip_binding_modes(&mut pat_paren.pat),
+        Pat::Type(pat_type) => strip_binding_modes(&mut pat_type.pat),
+        _ => {}
+    }
 }
 
+/// 协程系统最多的参数数量
+const MAX_PARAMS: usize = 15;
+
 /// 转换函数体，处理yield表达式
+/// 
+/// 参数绑定在函数体开头声明一次，每次 yield 恢复后通过赋值重新获取，
+/// 因此无论 yield 出现在哪个表达式中，之后使用的参数都是这一次运行获取的
 fn transform_function_body(
     block: &syn::Block,
-    param_names: &[&syn::Ident],
-    _params_struct_name: &syn::Ident,
+    param_pats: &[Pat],
+    param_paths: &[syn::Index],
 ) -> proc_macro2::TokenStream {
-    // 生成参数获取代码
-    let get_params = quote! {
-        let params = __coroutine_input.data_mut();
-        #(let #param_names = &mut params.#param_names;)*
-    };
+    let mut bindings = Vec::new();
+    for pat in param_pats {
+        collect_bindings(pat, &mut bindings);
+    }
     
-    // 首先添加初始的参数获取
-    let mut new_stmts = vec![quote! { #get_params }];
+    let mut rewriter = YieldRewriter {
+        param_pats,
+        param_paths,
+        bindings: &bindings,
+        shadowed: Vec::new(),
+        stale: Vec::new(),
+        unshadowed: false,
+    };
+    let refresh_params = rewriter.refresh_params();
     
-    // 转换所有语句
-    let transformed_stmts = transform_statements(&block.stmts, &get_params);
-    new_stmts.extend(transformed_stmts);
+    // 转换所有的 yield 表达式，函数体结束后参数不再被使用，不需要重新获取
+    let mut block = block.clone();
+    for stmt in &mut block.stmts {
+        rewriter.visit_stmt_mut(stmt);
+    }
+    let stmts = &block.stmts;
     
     quote! {
-        #(#new_stmts)*
+        let mut __params;
+        #(let mut #bindings;)*
+        #refresh_params
+        #(#stmts)*
     }
 }
 
-/// 递归转换语句列表，处理所有的 yield 表达式
-fn transform_statements(
-    stmts: &[syn::Stmt],
-    get_params: &proc_macro2::TokenStream,
-) -> Vec<proc_macro2::TokenStream> {
-    let mut new_stmts = Vec::new();
-    
-    for stmt in stmts {
-        match stmt {
-            syn::Stmt::Local(local) => {
-                // 处理 let x = yield expr;
-                if let Some(init) = &local.init {
-                    if let syn::Expr::Yield(yield_expr) = &*init.expr {
-                        if let Some(yielded_expr) = &yield_expr.expr {
-                            let pat = &local.pat;
-                            
-                            // 生成新的语句序列
-                            new_stmts.push(quote! {
-                                __coroutine_input = yield #yielded_expr;
-                            });
-                            new_stmts.push(quote! {
-                                let #pat = __coroutine_input.result();
-                            });
-                            // yield 后重新获取参数
-                            new_stmts.push(quote! { #get_params });
-                            continue;
-                        }
-                    } else if let syn::Expr::Macro(mac_expr) = &*init.expr {
-                        // 兼容 yield_async! 宏
-                        if is_yield_macro(&mac_expr.mac) {
-                            if let Ok(inner_expr) = mac_expr.mac.parse_body::<syn::Expr>() {
-                                let pat = &local.pat;
-                                
-                                new_stmts.push(quote! {
-                                    __coroutine_input = yield #inner_expr;
-                                });
-                                new_stmts.push(quote! {
-                                    let #pat = __coroutine_input.result();
-                                });
-                                new_stmts.push(quote! { #get_params });
-                                continue;
-                            }
-                        }
-                    }
-                }
-                // 其他情况保持原样
-                new_stmts.push(quote! { #stmt });
-            }
-            syn::Stmt::Expr(expr, semi) => {
-                // 处理独立的 yield expr 语句
-                if let syn::Expr::Yield(yield_expr) = expr {
-                    if let Some(yielded_expr) = &yield_expr.expr {
-                        new_stmts.push(quote! {
-                            __coroutine_input = yield #yielded_expr;
-                        });
-                        new_stmts.push(quote! {
-                            // 丢弃结果，不指定具体类型
-                            let _ = __coroutine_input.async_result.take();
-                        });
-                        new_stmts.push(quote! { #get_params });
-                        
-                        if semi.is_some() {
-                            // 保持原有的分号
-                        }
-                        continue;
-                    }
-                } else if let syn::Expr::Macro(mac_expr) = expr {
-                    // 兼容 yield_async! 宏
-                    if is_yield_macro(&mac_expr.mac) {
-                        if let Ok(inner_expr) = mac_expr.mac.parse_body::<syn::Expr>() {
-                            new_stmts.push(quote! {
-                                __coroutine_input = yield #inner_expr;
-                            });
-                            new_stmts.push(quote! {
-                                // 丢弃结果，不指定具体类型
-                                let _ = __coroutine_input.async_result.take();
-                            });
-                            new_stmts.push(quote! { #get_params });
-                            continue;
-                        }
-                    }
-                } else {
-                    // 递归处理表达式中的代码块
-                    let transformed_expr = transform_expression(expr, get_params);
-                    if semi.is_some() {
-                        new_stmts.push(quote! { #transformed_expr; });
-                    } else {
-                        new_stmts.push(quote! { #transformed_expr });
-                    }
-                    continue;
-                }
-                // 其他情况保持原样
-                new_stmts.push(quote! { #stmt });
+/// 收集模式中绑定的变量
+fn collect_bindings<'a>(pat: &'a Pat, bindings: &mut Vec<&'a syn::Ident>) {
+    match pat {
+        Pat::Ident(pat_ident) => {
+            bindings.push(&pat_ident.ident);
+            if let Some((_, subpat)) = &pat_ident.subpat {
+                collect_bindings(subpat, bindings);
             }
-            _ => {
-                // 递归处理其他类型的语句
-                let transformed_stmt = transform_statement(stmt, get_params);
-                new_stmts.push(transformed_stmt);
+        }
+        Pat::Tuple(pat_tuple) => pat_tuple.elems.iter().for_each(|pat| collect_bindings(pat, bindings)),
+        Pat::TupleStruct(pat_tuple_struct) => pat_tuple_struct
+            .elems
+            .iter()
+            .for_each(|pat| collect_bindings(pat, bindings)),
+        Pat::Struct(pat_struct) => pat_struct
+            .fields
+            .iter()
+            .for_each(|field| collect_bindings(&field.pat, bindings)),
+        Pat::Slice(pat_slice) => pat_slice.elems.iter().for_each(|pat| collect_bindings(pat, bindings)),
+        Pat::Or(pat_or) => {
+            if let Some(pat) = pat_or.cases.first() {
+                collect_bindings(pat, bindings);
             }
         }
+        Pat::Paren(pat_paren) => collect_bindings(&pat_paren.pat, bindings),
+        Pat::Type(pat_type) => collect_bindings(&pat_type.pat, bindings),
+        _ => {}
     }
-    
-    new_stmts
 }
 
-/// 转换单个语句
-fn transform_statement(
-    stmt: &syn::Stmt,
-    get_params: &proc_macro2::TokenStream,
-) -> proc_macro2::TokenStream {
-    match stmt {
-        syn::Stmt::Expr(expr, semi) => {
-            let transformed_expr = transform_expression(expr, get_params);
-            if semi.is_some() {
-                quote! { #transformed_expr; }
-            } else {
-                quote! { #transformed_expr }
-            }
-        }
-        _ => quote! { #stmt },
-    }
+/// 将协程体中的 yield 表达式转换为 yield 一个 Future 并重新获取参数的代码块
+/// 
+/// 转换时记录被函数体中的绑定遮蔽的参数，yield 恢复后只重新获取仍然可见的参数；
+/// 遮蔽期间经过了 yield 的参数在遮蔽它的作用域结束后重新获取
+struct YieldRewriter<'a> {
+    param_pats: &'a [Pat],
+    param_paths: &'a [syn::Index],
+    bindings: &'a [&'a syn::Ident],
+    shadowed: Vec<syn::Ident>,
+    /// 被遮蔽时经过了 yield，仍然借用着上一次运行的参数
+    stale: Vec<syn::Ident>,
+    /// 当前作用域中是否有 `stale` 中的参数重新可见
+    unshadowed: bool,
 }
 
-/// 递归转换表达式，处理嵌套的代码块
-fn transform_expression(
-    expr: &syn::Expr,
-    get_params: &proc_macro2::TokenStream,
-) -> proc_macro2::TokenStream {
-    match expr {
-        // 处理代码块
-        syn::Expr::Block(block_expr) => {
-            let transformed_stmts = transform_statements(&block_expr.block.stmts, get_params);
-            quote! {
-                {
-                    #(#transformed_stmts)*
-                }
-            }
-        }
-        // 处理 if 表达式
-        syn::Expr::If(if_expr) => {
-            let cond = &if_expr.cond;
-            let then_branch_stmts = transform_statements(&if_expr.then_branch.stmts, get_params);
-            
-            if let Some((_, else_branch)) = &if_expr.else_branch {
-                let else_transformed = transform_expression(else_branch, get_params);
-                quote! {
-                    if #cond {
-                        #(#then_branch_stmts)*
-                    } else #else_transformed
-                }
-            } else {
-                quote! {
-                    if #cond {
-                        #(#then_branch_stmts)*
-                    }
-                }
-            }
-        }
-        // 处理 while 循环
-        syn::Expr::While(while_expr) => {
-            let cond = &while_expr.cond;
-            let body_stmts = transform_statements(&while_expr.body.stmts, get_params);
-            quote! {
-                while #cond {
-                    #(#body_stmts)*
-                }
+impl YieldRewriter<'_> {
+    /// 生成重新获取参数的代码
+    fn refresh_params(&self) -> proc_macro2::TokenStream {
+        let rebind_params = self.rebind_params();
+        quote! {
+            #[allow(unused_assignments)]
+            {
+                __params = __coroutine_input.params();
+                #rebind_params
             }
         }
-        // 处理 loop 循环
-        syn::Expr::Loop(loop_expr) => {
-            let body_stmts = transform_statements(&loop_expr.body.stmts, get_params);
-            quote! {
-                loop {
-                    #(#body_stmts)*
-                }
-            }
+    }
+    
+    /// 从 `__params` 重新绑定仍然可见的参数
+    fn rebind_params(&self) -> proc_macro2::TokenStream {
+        let param_pats = self.param_pats;
+        let param_paths = self.param_paths;
+        let visible: Vec<_> = self
+            .bindings
+            .iter()
+            .filter(|binding| !self.shadowed.iter().any(|shadowed| shadowed == **binding))
+            .collect();
+        // 没有需要重新绑定的参数时不生成 `() = { () };`
+        if visible.is_empty() {
+            return quote! {};
         }
-        // 处理 for 循环
-        syn::Expr::ForLoop(for_expr) => {
-            let pat = &for_expr.pat;
-            let iter = &for_expr.expr;
-            let body_stmts = transform_statements(&for_expr.body.stmts, get_params);
-            quote! {
-                for #pat in #iter {
-                    #(#body_stmts)*
-                }
-            }
+        quote! {
+            (#(#visible,)*) = {
+                #(let #param_pats = &mut __params.#param_paths;)*
+                (#(#visible,)*)
+            };
         }
-        // 处理 match 表达式
-        syn::Expr::Match(match_expr) => {
-            let matched = &match_expr.expr;
-            let mut arms = Vec::new();
-            
-            for arm in &match_expr.arms {
-                let pat = &arm.pat;
-                let guard = arm.guard.as_ref().map(|(_, guard)| quote! { if #guard });
-                let body = transform_expression(&arm.body, get_params);
-                let comma = if arm.comma.is_some() { quote! {,} } else { quote! {} };
-                
-                arms.push(quote! {
-                    #pat #guard => #body #comma
-                });
-            }
-            
-            quote! {
-                match #matched {
-                    #(#arms)*
-                }
+    }
+    
+    /// 记录模式中的绑定，它们会遮蔽同名的参数直到所在的作用域结束
+    fn shadow(&mut self, pat: &Pat) {
+        let mut bindings = Vec::new();
+        collect_bindings(pat, &mut bindings);
+        self.shadowed.extend(bindings.into_iter().cloned());
+    }
+    
+    /// yield 时记录被遮蔽而无法重新获取的参数
+    fn mark_stale(&mut self) {
+        for binding in self.bindings {
+            if self.shadowed.iter().any(|shadowed| shadowed == *binding)
+                && !self.stale.iter().any(|stale| stale == *binding)
+            {
+                self.stale.push((*binding).clone());
             }
         }
-        // 其他表达式保持不变
-        _ => quote! { #expr },
     }
-}
-
-/// 检查是否是yield!宏
-fn is_yield_macro(mac: &syn::Macro) -> bool {
-    mac.path.segments.last().map(|seg| {
-        seg.ident == "yield" || seg.ident == "yield_async"
-    }).unwrap_or(false)
-}
-
-/// 检查类型需要哪些生命周期参数
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct LifetimeRequirement {
-    needs_w: bool,
-    needs_s: bool,
-}
-
-impl LifetimeRequirement {
-    fn none() -> Self {
-        Self { needs_w: false, needs_s: false }
+    
+    /// 移除作用域中的绑定，记录重新可见的 `stale` 中的参数
+    fn truncate(&mut self, scope: usize) {
+        self.shadowed.truncate(scope);
+        let stale = self.stale.len();
+        self.stale.retain(|stale| self.shadowed.contains(stale));
+        self.unshadowed |= self.stale.len() < stale;
     }
     
-    fn merge(&mut self, other: Self) {
-        self.needs_w |= other.needs_w;
-        self.needs_s |= other.needs_s;
+    /// 在作用域的值计算完后重新绑定参数，让遮蔽期间经过了 yield 的参数重新可用
+    fn restore_params(&self, scope_expr: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let rebind_params = self.rebind_params();
+        quote! {
+            let __scope_result = #scope_expr;
+            #[allow(unused_assignments, unreachable_code)]
+            {
+                #rebind_params
+            }
+            __scope_result
+        }
     }
 }
 
-/// 分析类型并返回其生命周期需求
-fn analyze_lifetime_requirements(ty: &syn::Type) -> LifetimeRequirement {
-    use syn::{Type, PathArguments};
-    
-    match ty {
-        Type::Reference(type_ref) => {
-            // 引用类型继承其内部类型的生命周期需求
-            analyze_lifetime_requirements(&type_ref.elem)
-        }
-        
-        Type::Tuple(type_tuple) => {
-            // 元组类型合并所有元素的生命周期需求
-            let mut req = LifetimeRequirement::none();
-            for elem in &type_tuple.elems {
-                req.merge(analyze_lifetime_requirements(elem));
+impl VisitMut for YieldRewriter<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        let yielded_expr = match expr {
+            // 闭包和 async 块中的代码不属于这个协程
+            syn::Expr::Closure(_) | syn::Expr::Async(_) => return,
+            // async 后端中协程体是一个 async 块，`.await` 会绕过 yield 而不被驱动系统唤醒
+            syn::Expr::Await(expr_await) => {
+                let span = expr_await.await_token.span;
+                *expr = syn::Expr::Verbatim(quote_spanned! {span=>
+                    ::core::compile_error!("`.await` cannot be used in coroutine_system functions, use `yield` to wait for a future")
+                });
+                return;
             }
-            req
+            syn::Expr::Yield(syn::ExprYield { expr: Some(yielded_expr), .. }) => {
+                Some((**yielded_expr).clone())
+            }
+            // 兼容 yield_async! 宏
+            syn::Expr::Macro(mac_expr) => parse_yield_macro(&mac_expr.mac),
+            _ => None,
+        };
+        if let Some(mut yielded_expr) = yielded_expr {
+            // 被 yield 的表达式中也可能有 yield
+            self.visit_expr_mut(&mut yielded_expr);
+            self.mark_stale();
+            *expr = syn::Expr::Verbatim(yield_expression(&yielded_expr, &self.refresh_params()));
+            return;
         }
         
-        Type::Path(type_path) => {
-            let mut req = LifetimeRequirement::none();
-            
-            // 检查路径中的每个段
-            for segment in &type_path.path.segments {
-                let ident_str = segment.ident.to_string();
-                
-                // 根据类型名确定生命周期需求
-                match ident_str.as_str() {
-                    "Commands" => req.merge(LifetimeRequirement { needs_w: true, needs_s: true }),
-                    "Query" => req.merge(LifetimeRequirement { needs_w: true, needs_s: true }),
-                    "Res" | "ResMut" => req.merge(LifetimeRequirement { needs_w: true, needs_s: false }),
-                    "Local" => req.merge(LifetimeRequirement { needs_w: false, needs_s: true }),
-                    "EventWriter" => req.merge(LifetimeRequirement { needs_w: true, needs_s: false }),
-                    "EventReader" => req.merge(LifetimeRequirement { needs_w: true, needs_s: true }),
-                    _ => {}
+        // 按作用域记录遮蔽参数的绑定
+        let scope = self.shadowed.len();
+        let unshadowed = std::mem::take(&mut self.unshadowed);
+        match expr {
+            // 条件中 let 的绑定在 then 分支中可见
+            syn::Expr::If(expr_if) => {
+                self.visit_expr_mut(&mut expr_if.cond);
+                self.visit_block_mut(&mut expr_if.then_branch);
+                self.truncate(scope);
+                if let Some((_, else_branch)) = &mut expr_if.else_branch {
+                    self.visit_expr_mut(else_branch);
                 }
-                
-                // 递归分析泛型参数
-                if let PathArguments::AngleBracketed(args) = &segment.arguments {
-                    for arg in &args.args {
-                        if let syn::GenericArgument::Type(inner_ty) = arg {
-                            req.merge(analyze_lifetime_requirements(inner_ty));
-                        }
+            }
+            syn::Expr::While(expr_while) => {
+                self.visit_expr_mut(&mut expr_while.cond);
+                self.visit_block_mut(&mut expr_while.body);
+            }
+            // 条件中的 let 表达式，绑定在所在的 if、while 或 match 分支结束前可见
+            syn::Expr::Let(expr_let) => {
+                self.visit_expr_mut(&mut expr_let.expr);
+                self.shadow(&expr_let.pat);
+                self.unshadowed = unshadowed;
+                return;
+            }
+            syn::Expr::ForLoop(expr_for) => {
+                self.visit_expr_mut(&mut expr_for.expr);
+                self.shadow(&expr_for.pat);
+                self.visit_block_mut(&mut expr_for.body);
+            }
+            syn::Expr::Match(expr_match) => {
+                self.visit_expr_mut(&mut expr_match.expr);
+                for arm in &mut expr_match.arms {
+                    self.shadow(&arm.pat);
+                    if let Some((_, guard)) = &mut arm.guard {
+                        self.visit_expr_mut(guard);
                     }
+                    self.visit_expr_mut(&mut arm.body);
+                    self.truncate(scope);
                 }
             }
-            
-            req
+            // 其他表达式不引入作用域
+            _ => {
+                self.unshadowed = unshadowed;
+                return visit_mut::visit_expr_mut(self, expr);
+            }
+        }
+        self.truncate(scope);
+        if std::mem::replace(&mut self.unshadowed, unshadowed) {
+            let restore = self.restore_params(quote! { #expr });
+            *expr = syn::Expr::Verbatim(quote! { { #restore } });
         }
-        
-        _ => LifetimeRequirement::none(),
     }
-}
-
-/// 为已知的Bevy类型添加生命周期参数
-fn add_lifetimes_to_type(ty: &syn::Type) -> syn::Type {
-    use syn::{Type, TypePath, PathArguments, GenericArgument, AngleBracketedGenericArguments};
-    use syn::parse_quote;
     
-    match ty {
-        // 处理引用类型 &T 或 &mut T
-        Type::Reference(type_ref) => {
-            let elem = add_lifetimes_to_type(&type_ref.elem);
-            let lifetime = type_ref.lifetime.clone()
-                .unwrap_or_else(|| parse_quote! { 'static });
-            
-            Type::Reference(syn::TypeReference {
-                and_token: type_ref.and_token,
-                lifetime: Some(lifetime),
-                mutability: type_ref.mutability,
-                elem: Box::new(elem),
-            })
+    fn visit_block_mut(&mut self, block: &mut syn::Block) {
+        let scope = self.shadowed.len();
+        let unshadowed = std::mem::take(&mut self.unshadowed);
+        for stmt in &mut block.stmts {
+            self.visit_stmt_mut(stmt);
         }
-        
-        // 处理元组类型 (A, B, C)
-        Type::Tuple(type_tuple) => {
-            let elems = type_tuple.elems.iter()
-                .map(|elem| add_lifetimes_to_type(elem))
-                .collect();
-            
-            Type::Tuple(syn::TypeTuple {
-                paren_token: type_tuple.paren_token,
-                elems,
-            })
+        self.truncate(scope);
+        if std::mem::replace(&mut self.unshadowed, unshadowed) {
+            let stmts = &block.stmts;
+            let restore = self.restore_params(quote! { { #(#stmts)* } });
+            block.stmts = vec![syn::Stmt::Expr(syn::Expr::Verbatim(restore), None)];
         }
-        
-        // 处理路径类型 A::B::C<T>
-        Type::Path(type_path) => {
-            let mut path = type_path.path.clone();
-            
-            // 处理路径中的每个段
-            for segment in &mut path.segments {
-                let ident_str = segment.ident.to_string();
-                
-                // 检查是否是需要生命周期的Bevy类型
-                let needs_lifetimes = match ident_str.as_str() {
-                    "Commands" | "Query" => true,
-                    "Local" => true,
-                    "Res" | "ResMut" | "EventWriter" => true,
-                    "EventReader" => true,
-                    _ => false,
-                };
-                
-                match &mut segment.arguments {
-                    PathArguments::None => {
-                        if needs_lifetimes {
-                            // 为这些类型添加生命周期
-                            if ident_str == "Res" || ident_str == "ResMut" || ident_str == "EventWriter" {
-                                // Res, ResMut 和 EventWriter 只需要一个生命周期 'w
-                                segment.arguments = PathArguments::AngleBracketed(
-                                    parse_quote! { <'w> }
-                                );
-                            } else if ident_str == "Local" {
-                                // Local 只需要一个生命周期 's
-                                segment.arguments = PathArguments::AngleBracketed(
-                                    parse_quote! { <'s> }
-                                );
-                            } else {
-                                // Commands, Query, EventReader 需要两个生命周期
-                                segment.arguments = PathArguments::AngleBracketed(
-                                    parse_quote! { <'w, 's> }
-                                );
-                            }
-                        }
-                    }
-                    PathArguments::AngleBracketed(args) => {
-                        let mut new_args = args.clone();
-                        
-                        // 递归处理所有泛型参数
-                        new_args.args = new_args.args.into_iter().map(|arg| {
-                            match arg {
-                                GenericArgument::Type(ty) => {
-                                    GenericArgument::Type(add_lifetimes_to_type(&ty))
-                                }
-                                other => other,
-                            }
-                        }).collect();
-                        
-                        // 如果是需要生命周期的类型，在开头插入生命周期
-                        if needs_lifetimes {
-                            let mut final_args = syn::punctuated::Punctuated::new();
-                            
-                            // 插入生命周期
-                            if ident_str == "Res" || ident_str == "ResMut" || ident_str == "EventWriter" {
-                                final_args.push(parse_quote! { 'w });
-                            } else if ident_str == "Local" {
-                                final_args.push(parse_quote! { 's });
-                            } else if ident_str == "Query" || ident_str == "Commands" || ident_str == "EventReader" {
-                                final_args.push(parse_quote! { 'w });
-                                final_args.push(parse_quote! { 's });
-                            }
-                            
-                            // 添加处理后的参数
-                            final_args.extend(new_args.args);
-                            
-                            segment.arguments = PathArguments::AngleBracketed(
-                                AngleBracketedGenericArguments {
-                                    colon2_token: args.colon2_token,
-                                    lt_token: args.lt_token,
-                                    args: final_args,
-                                    gt_token: args.gt_token,
-                                }
-                            );
-                        } else {
-                            segment.arguments = PathArguments::AngleBracketed(new_args);
-                        }
-                    }
-                    _ => {}
-                }
+    }
+    
+    fn visit_local_mut(&mut self, local: &mut syn::Local) {
+        // 初始化表达式和 else 分支中还看不到 let 的绑定
+        if let Some(init) = &mut local.init {
+            self.visit_expr_mut(&mut init.expr);
+            if let Some((_, diverge)) = &mut init.diverge {
+                self.visit_expr_mut(diverge);
             }
-            
-            Type::Path(TypePath {
-                qself: type_path.qself.clone(),
-                path,
-            })
         }
-        
-        // 其他类型保持不变
-        _ => (*ty).clone(),
+        self.shadow(&local.pat);
+    }
+    
+    fn visit_stmt_mut(&mut self, stmt: &mut syn::Stmt) {
+        // 语句位置的 yield_async!(...); 被解析为宏语句
+        if let syn::Stmt::Macro(stmt_mac) = stmt
+            && is_yield_macro(&stmt_mac.mac)
+        {
+            let mut expr = syn::Expr::Macro(syn::ExprMacro {
+                attrs: stmt_mac.attrs.clone(),
+                mac: stmt_mac.mac.clone(),
+            });
+            self.visit_expr_mut(&mut expr);
+            *stmt = syn::Stmt::Expr(expr, stmt_mac.semi_token);
+            return;
+        }
+        visit_mut::visit_stmt_mut(self, stmt);
+    }
+    
+    // 内部定义的函数等项不属于这个协程
+    fn visit_item_mut(&mut self, _item: &mut syn::Item) {}
+}
+
+/// 生成 yield 一个 Future 并返回其输出的代码块
+/// 
+/// Future 的输出类型通过 `YieldOutput` 标记传递给 `take_result`，
+/// 因此 `let` 的类型标注与 Future 的 `Output` 不匹配时会在编译期报错
+fn yield_expression(
+    yielded_expr: &syn::Expr,
+    refresh_params: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let span = yielded_expr.span();
+    let take_result = quote_spanned! {span=>
+        __coroutine_input.take_result(__yield_output)
+    };
+    // async 后端中 yield 是对 `CoroutineYielder` 的 await
+    let resume = if cfg!(feature = "async-backend") {
+        quote! { __yielder.yield_future(__yield_future).await }
+    } else {
+        quote! { yield __yield_future }
+    };
+    // 调用带有 yield 表达式的位置，协程的运行时信息据此记录 yield 的位置
+    let typed_future = quote_spanned! {span=>
+        ::bevy_coroutine_system::typed_coroutine_future(#yielded_expr)
+    };
+    quote! {
+        {
+            let (__yield_future, __yield_output) = #typed_future;
+            __coroutine_input = #resume;
+            let __yield_result = #take_result;
+            // yield 后重新获取参数
+            #refresh_params
+            __yield_result
+        }
+    }
+}
+
+/// 检查是否是yield!宏
+fn is_yield_macro(mac: &syn::Macro) -> bool {
+    mac.path.segments.last().map(|seg| {
+        seg.ident == "yield" || seg.ident == "yield_async"
+    }).unwrap_or(false)
+}
+
+/// 如果是yield!宏，解析出其中的表达式
+fn parse_yield_macro(mac: &syn::Macro) -> Option<syn::Expr> {
+    if !is_yield_macro(mac) {
+        return None;
     }
+    mac.parse_body::<syn::Expr>().ok()
 }
@@ -0,0 +1,157 @@
+//! `#[coroutine_system(...)]` 的参数和 `coroutine_systems!` 的输入
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Expr, ItemFn, Lit, LitStr, Meta, Token};
+
+/// 解析后的宏参数
+#[derive(Default)]
+pub(crate) struct CoroutineOptions {
+    /// 显式指定的协程标识符
+    pub name: Option<LitStr>,
+    schedule: Option<Expr>,
+    retrigger: Option<syn::Ident>,
+    repeat: Option<bool>,
+    state: Option<Expr>,
+    tags: Option<Vec<LitStr>>,
+    priority: Option<Expr>,
+}
+
+impl Parse for CoroutineOptions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = CoroutineOptions::default();
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            let key = meta
+                .path()
+                .get_ident()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            match (key.as_str(), &meta) {
+                ("name", Meta::NameValue(name_value)) => {
+                    set_once(&mut options.name, string_literal(&name_value.value)?, &meta)?;
+                }
+                ("schedule", Meta::NameValue(name_value)) => {
+                    set_once(&mut options.schedule, name_value.value.clone(), &meta)?;
+                }
+                ("retrigger", Meta::NameValue(name_value)) => {
+                    let policy = retrigger_policy(&name_value.value)?;
+                    set_once(&mut options.retrigger, policy, &meta)?;
+                }
+                ("repeat", Meta::Path(_)) => set_once(&mut options.repeat, true, &meta)?,
+                ("repeat", Meta::NameValue(name_value)) => {
+                    let Expr::Lit(syn::ExprLit { lit: Lit::Bool(repeat), .. }) = &name_value.value else {
+                        return Err(syn::Error::new_spanned(&name_value.value, "expected `true` or `false`"));
+                    };
+                    set_once(&mut options.repeat, repeat.value, &meta)?;
+                }
+                ("state", Meta::NameValue(name_value)) => {
+                    set_once(&mut options.state, name_value.value.clone(), &meta)?;
+                }
+                ("tags", Meta::NameValue(name_value)) => {
+                    let Expr::Array(array) = &name_value.value else {
+                        return Err(syn::Error::new_spanned(
+                            &name_value.value,
+                            "expected an array of string literals, e.g. `tags = [\"ui\", \"cutscene\"]`",
+                        ));
+                    };
+                    let tags = array.elems.iter().map(string_literal).collect::<syn::Result<_>>()?;
+                    set_once(&mut options.tags, tags, &meta)?;
+                }
+                ("priority", Meta::NameValue(name_value)) => {
+                    set_once(&mut options.priority, name_value.value.clone(), &meta)?;
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &meta,
+                        "unknown coroutine_system option, expected one of \
+                         `name = \"...\"`, `schedule = ...`, `retrigger = Resume | Ignore | Restart`, \
+                         `repeat`, `state = ...`, `tags = [...]`, `priority = ...`",
+                    ));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl CoroutineOptions {
+    /// 生成构造 `CoroutineConfig` 的表达式
+    pub fn config(&self) -> TokenStream {
+        let schedule = self.schedule.iter();
+        let retrigger = self.retrigger.iter();
+        let repeat = self.repeat.iter();
+        let state = self.state.iter();
+        let tags = self.tags.iter().map(|tags| quote! { &[#(#tags),*] });
+        let priority = self.priority.iter();
+        quote! {
+            ::bevy_coroutine_system::CoroutineConfig::default()
+                #(.schedule(#schedule))*
+                #(.retrigger(::bevy_coroutine_system::CoroutineRetrigger::#retrigger))*
+                #(.repeat(#repeat))*
+                #(.state_scope(#state))*
+                #(.tags(#tags))*
+                #(.priority(#priority))*
+        }
+    }
+}
+
+/// `coroutine_systems!` 中的协程函数及其参数
+pub(crate) struct CoroutineItems(pub Vec<(CoroutineOptions, ItemFn)>);
+
+impl Parse for CoroutineItems {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut items = Vec::new();
+        while !input.is_empty() {
+            let mut item: ItemFn = input.parse()?;
+            // 函数上的 #[coroutine_system(...)] 只用来传递参数
+            let mut options = None;
+            let mut attrs = Vec::new();
+            for attr in std::mem::take(&mut item.attrs) {
+                if !attr.path().is_ident("coroutine_system") {
+                    attrs.push(attr);
+                    continue;
+                }
+                let parsed = match &attr.meta {
+                    Meta::Path(_) => CoroutineOptions::default(),
+                    Meta::List(list) => syn::parse2(list.tokens.clone())?,
+                    Meta::NameValue(_) => {
+                        return Err(syn::Error::new_spanned(attr, "expected `#[coroutine_system(...)]`"));
+                    }
+                };
+                if options.replace(parsed).is_some() {
+                    return Err(syn::Error::new_spanned(attr, "duplicate #[coroutine_system] attribute"));
+                }
+            }
+            item.attrs = attrs;
+            items.push((options.unwrap_or_default(), item));
+        }
+        Ok(Self(items))
+    }
+}
+
+fn set_once<T>(option: &mut Option<T>, value: T, meta: &Meta) -> syn::Result<()> {
+    if option.is_some() {
+        return Err(syn::Error::new_spanned(meta, "duplicate coroutine_system option"));
+    }
+    *option = Some(value);
+    Ok(())
+}
+
+fn string_literal(expr: &Expr) -> syn::Result<LitStr> {
+    match expr {
+        Expr::Lit(syn::ExprLit { lit: Lit::Str(lit), .. }) => Ok(lit.clone()),
+        expr => Err(syn::Error::new_spanned(expr, "expected a string literal")),
+    }
+}
+
+fn retrigger_policy(expr: &Expr) -> syn::Result<syn::Ident> {
+    if let Expr::Path(expr_path) = expr
+        && let Some(ident) = expr_path.path.get_ident()
+        && (ident == "Resume" || ident == "Ignore" || ident == "Restart")
+    {
+        return Ok(ident.clone());
+    }
+    Err(syn::Error::new_spanned(expr, "expected `Resume`, `Ignore` or `Restart`"))
+}